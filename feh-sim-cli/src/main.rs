@@ -0,0 +1,1110 @@
+//! Command-line front end for the simulator, for running large batches of
+//! simulations from scripts instead of the browser.
+
+use std::env;
+use std::mem;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use strum::IntoEnumIterator;
+
+use feh_sim_core::counter::{Count, Counter};
+use feh_sim_core::goal::{
+    copies_for_merge_level, CustomGoal, Goal, GoalGroup, GoalKind, GoalNode, GoalPart, GoalPreset,
+    PartTarget, MAX_MERGE_LEVEL,
+};
+use feh_sim_core::sim::campaign::{self, Campaign};
+use feh_sim_core::sim::optimal::{self, Objective};
+use feh_sim_core::sim::{exact, parallel, Sim};
+use feh_sim_core::strategy::Strategy;
+use feh_sim_core::{
+    legacy, stats, Banner, BannerKind, BannerTemplate, Color, IncomeProfile, Pool, PoolSizes,
+    Progress, PERMALINK_VERSION,
+};
+
+const USAGE: &str = "\
+Usage: feh-sim [OPTIONS] [banner=<DATA>] [goal=<DATA>] [progress=<DATA>]
+               [<PERMALINK>]
+
+The banner=<DATA>, goal=<DATA> and progress=<DATA> arguments take the same
+values as the site's permalinks, and a whole permalink can be passed in as
+well. Options that come after them modify the settings they loaded.
+
+Banner options:
+    --template <NAME>           Load a saved banner by name, including the
+                                names of its focus units. Give it before any
+                                other banner options, which it resets.
+    --kind <KIND>               Kind of banner, which sets the usual rates,
+                                focus units, spark and focus charges for it.
+                                One of new-heroes, legendary, mythic, remix,
+                                hero-fest, weekly-revival, double-special,
+                                attuned or custom [default: new-heroes]
+    --rates <FOCUS>/<FIVESTAR>  Starting 5* rates, in percent, with the rest
+                                split between 4* and 3* units as on a normal
+                                banner [default: 3/3]
+    --rates <FOCUS>/<FIVESTAR>/<FOURSTAR_FOCUS>/<FOURSTAR>/<THREESTAR>[/<FOURSTAR_SPECIAL>]
+                                Starting rates for every pool, in percent,
+                                adding up to 100. The special 4* rate is for
+                                4* copies of the 5* focus units.
+    --focus <R>,<B>,<G>,<C>     Number of 5* focus units of each color
+                                [default: 1,1,1,1]
+    --no-charges                Disable focus charges
+    --fourstar-focus <COLOR>    Add a 4* focus unit of this color, which is
+                                also the first 5* focus unit of its color
+                                that isn't already one. Can be given multiple
+                                times. Also switches to the usual 4* focus
+                                rates unless the rates already include them.
+    --spark <N>                 Number of summons needed for a spark
+                                [default: 40]
+    --no-spark                  Disable sparks
+    --pool <DATE>               Use the summoning pool sizes from this month,
+                                as YYYY-MM [default: the latest]
+    --pool-size <STARS>:<R>,<B>,<G>,<C>
+                                Override the number of units in the non-focus
+                                5*, 4* or 3* pool, with - for a color that
+                                keeps the usual size. Can be given once for
+                                each rarity.
+
+Goal options:
+    --preset <NAME>             Goal preset, e.g. AnyFocus or RedFocus
+                                [default: AnyFocus]
+    --count <N>                 Number of copies of each unit in the preset,
+                                or +N to reach merge level N [default: 1]
+    --part <N>:<COLOR>[:4|:pool]
+                                Add a custom goal part for N copies of a
+                                specific focus unit, or +N to reach merge
+                                level N, with :4 for a 4* focus unit or :pool
+                                for a unit in the non-focus 5* pool. <N>:any
+                                counts N 5* units of any kind. The color can
+                                be replaced by the name of a focus unit from
+                                --template to pick that unit. Can be given
+                                multiple times.
+    --group <KIND>              Start a nested group of custom goal parts,
+                                which ends at the next --end-group. KIND is
+                                any, all, or a number K for at least K of the
+                                group's parts. Groups can be nested, and count
+                                as a single part of the group around them.
+    --end-group                 End the innermost nested group
+    --all                       Require every custom goal part instead of any
+    --at-least <K>              Require at least K of the custom goal parts
+    --fourstar-merges           Count 4* copies of the 5* focus units from the
+                                special 4* rate towards the custom goal parts
+    --budget <ORBS>             Also print the chance of reaching the goal with
+                                this many orbs, and the average number of goal
+                                copies gotten when they run out first
+
+Strategy options:
+    --strategy <NAME>           Which stones to pull in each session, one of
+                                goal-colors, full-circle, only:<COLOR>,
+                                extra-after:<SUMMONS> for one extra stone once
+                                the pity rate has gone that long without a
+                                reset, or off-color:<COLOR> for a stone of
+                                that color when the goal's colors don't show
+                                up [default: goal-colors]
+    --compare                   Print a line of results for each kind of
+                                strategy instead
+    --optimize                  Work out the best stones to pull in every
+                                situation, and print how much better that does
+                                than the strategy: fewer orbs on average, or a
+                                better chance of success within --budget
+    --decisions                 With --optimize, print every situation where
+                                the best choice is something other than the
+                                stones of the goal's colors, or a single stone
+                                when there are none
+
+Progress options:
+    --pity <N>                  Summons since the 5* rates were last reset
+    --charges <N>               Number of focus charges already collected
+    --spark-count <N>           Summons already made towards the next spark
+    --owned <N>,...             Copies already owned of each part of the goal,
+                                in order, or +N for a unit at merge level N
+
+Campaign options:
+    --then                      End the settings for one banner and start on
+                                the next one, turning the run into a campaign
+                                over several banners in order. Banner, goal
+                                and progress options go back to their defaults
+                                for each banner, while the rest are shared.
+                                Each banner is summoned on until its goal is
+                                reached or the orbs run out, and the chance of
+                                reaching each goal and all of them is printed.
+    --income <ORBS>             Orbs gained before the current banner starts,
+                                on top of what is left from the ones before it
+    --limit <ORBS>              Stop summoning on the current banner after
+                                spending this many orbs on it, saving the rest
+                                for the banners after it. 0 skips the banner.
+    --weight <W>                How much reaching the current banner's goal is
+                                worth to --optimize [default: 1]
+    --optimize                  With --then, search for the --limit on each
+                                banner that gives the best total of each
+                                goal's chance of success times its weight, and
+                                print the best plans found
+    --step <ORBS>               With --optimize and --then, the limits tried
+                                are multiples of this [default: 20]
+    --budget <ORBS>             With --then, the orbs available at the start
+                                of the campaign
+
+Income options:
+    --profile <NAME>            Estimate how many days of saving it takes to
+                                reach each percentile, starting from a rough
+                                profile of the income of a player who is
+                                free-to-play or paying. One of f2p or paying.
+                                Give it before the options below, which
+                                adjust it and also turn on the estimate.
+    --saved <ORBS>              Orbs already saved up [default: 0]
+    --daily <ORBS>              Orbs from login bonuses and daily rewards per
+                                day, which can be a fraction [default: 1]
+    --monthly-quests <ORBS>     Orbs from the monthly quests [default: 15]
+    --events <ORBS>             Orbs from events and other rewards in a
+                                typical month [default: 120]
+    --purchased <ORBS>          Orbs bought in a typical month [default: 0,
+                                or 140 for paying]
+
+Run options:
+    -n, --samples <N>           Number of simulations to run [default: 10000]
+    -t, --time <SECONDS>        Run simulations for this long instead
+    --exact                     Calculate the exact distribution instead of
+                                running simulations
+    -p, --percentiles <P>,...   Percentiles to print [default: 25,50,75,90,99]
+    -s, --seed <N>              Seed for the random number generator, to
+                                reproduce an earlier run [default: random]
+    -j, --threads <N>           Number of threads to run simulations on
+                                [default: number of CPUs]
+    -h, --help                  Print this message";
+
+/// The granularity of the stop-loss limits tried on a campaign unless --step
+/// says otherwise.
+const DEFAULT_LIMIT_STEP: u32 = 20;
+
+/// How long to spend getting the results.
+enum RunLength {
+    Samples(u64),
+    Time(Duration),
+    Exact,
+}
+
+/// Everything needed to perform a run, parsed from the command line.
+struct Options {
+    banner: Banner,
+    pool_sizes: PoolSizes,
+    goal: Goal,
+    run_length: RunLength,
+    percentiles: Vec<f32>,
+    budget: Option<u32>,
+    /// Where orbs come from over time, if the days of saving should be
+    /// printed.
+    income: Option<IncomeProfile>,
+    progress: Progress,
+    strategy: Strategy,
+    compare: bool,
+    optimize: bool,
+    decisions: bool,
+    seed: u64,
+    threads: usize,
+    /// Every banner of a campaign, in order, or nothing if there is only the
+    /// one banner.
+    campaign: Vec<CampaignBanner>,
+    /// The campaign settings of the banner that is being read.
+    stage: StageOptions,
+    /// The granularity of the stop-loss limits that --optimize tries on a
+    /// campaign, if given.
+    limit_step: Option<u32>,
+}
+
+/// One banner of a campaign given with --then.
+struct CampaignBanner {
+    banner: Banner,
+    goal: Goal,
+    progress: Progress,
+    stage: StageOptions,
+}
+
+/// Settings for how a banner fits into a campaign.
+struct StageOptions {
+    /// Orbs gained before the banner starts.
+    income: u32,
+    /// The most orbs to spend on the banner, if any.
+    limit: Option<u32>,
+    /// How much reaching the banner's goal counts for when optimizing.
+    weight: f64,
+}
+
+impl Default for StageOptions {
+    fn default() -> Self {
+        StageOptions {
+            income: 0,
+            limit: None,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Goal options that apply to the goal as a whole, which are held onto until
+/// the goal is complete.
+#[derive(Default)]
+struct GoalFlags {
+    preset_count: Option<u8>,
+    all_parts: bool,
+    at_least: Option<u8>,
+    fourstar_merges: bool,
+    /// The groups that have been started but not ended yet, innermost last.
+    open_groups: Vec<usize>,
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\nRun with --help for usage.", message);
+            process::exit(2);
+        }
+    };
+
+    if !options.campaign.is_empty() {
+        run_campaign(&options);
+        return;
+    }
+    if let Err(message) = check_banner(&options.banner, &options.goal, options.pool_sizes) {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+
+    let mut sim = Sim::new(options.banner.clone(), options.goal.clone(), options.seed);
+    sim.set_pool_sizes(options.pool_sizes);
+    sim.set_budget(options.budget);
+    sim.set_progress(options.progress.clone());
+    sim.set_strategy(options.strategy);
+    if options.compare {
+        compare_strategies(&sim, &options);
+        return;
+    }
+    if options.optimize {
+        optimize(&sim, &options);
+        return;
+    }
+
+    let data = match options.run_length {
+        RunLength::Samples(samples) => parallel::run(&sim, options.seed, samples, options.threads),
+        RunLength::Time(duration) => {
+            parallel::run_for(&sim, options.seed, duration, options.threads)
+        }
+        RunLength::Exact => {
+            // Far enough into the tail that the usual percentiles aren't affected.
+            let data = exact::orb_distribution(&sim, 1e-9);
+            print_results(&data, "exact", &options);
+            return;
+        }
+    };
+    let total: u64 = data.orbs.iter().map(|&count| count as u64).sum();
+    println!("seed\t{}", options.seed);
+    print_results(&data.orbs, &total.to_string(), &options);
+    if options.budget.is_some() {
+        println!("budget_copies\t{:.2}", stats::mean(&data.budget_copies));
+    }
+}
+
+/// Checks that a banner and goal can be simulated.
+fn check_banner(banner: &Banner, goal: &Goal, pool_sizes: PoolSizes) -> Result<(), String> {
+    if !goal.is_available(banner, pool_sizes) {
+        return Err("the goal is not possible on this banner".into());
+    }
+    if !banner.follows_kind_rules() {
+        return Err(format!(
+            "{} banners can't have this spark, focus charge, 4* focus or special 4* rate setting",
+            banner.kind
+        ));
+    }
+    if !banner.has_valid_rates() {
+        return Err(
+            "the rates on this banner need to add up to 100% and include the focus units".into(),
+        );
+    }
+    if !banner.has_valid_pools(pool_sizes) {
+        return Err("a summoning pool on this banner has no units in it".into());
+    }
+    Ok(())
+}
+
+/// Simulates summoning on each banner of a campaign in turn from the shared
+/// budget, then prints the chance of reaching each goal and all of them.
+fn run_campaign(options: &Options) {
+    let mut campaign = Campaign::new(options.budget.unwrap_or(0));
+    for (index, entry) in options.campaign.iter().enumerate() {
+        if let Err(message) = check_banner(&entry.banner, &entry.goal, options.pool_sizes) {
+            eprintln!("error: banner {}: {}", index + 1, message);
+            process::exit(1);
+        }
+        let mut sim = Sim::new(entry.banner.clone(), entry.goal.clone(), options.seed);
+        sim.set_pool_sizes(options.pool_sizes);
+        sim.set_progress(entry.progress.clone());
+        sim.set_strategy(options.strategy);
+        campaign.add_banner(sim, entry.stage.income);
+        campaign.set_limit(index, entry.stage.limit);
+    }
+    if options.optimize {
+        optimize_campaign(&campaign, options);
+        return;
+    }
+
+    let results = match options.run_length {
+        RunLength::Samples(samples) => {
+            parallel::run_campaign(&campaign, options.seed, samples, options.threads)
+        }
+        RunLength::Time(duration) => {
+            parallel::run_campaign_for(&campaign, options.seed, duration, options.threads)
+        }
+        RunLength::Exact => unreachable!("campaigns are checked for --exact when parsing"),
+    };
+    println!("seed\t{}", options.seed);
+    println!("samples\t{}", results.samples);
+    for (index, entry) in options.campaign.iter().enumerate() {
+        let goal = entry.goal.as_custom(&entry.banner);
+        println!("success_{}\t{:.4}", index + 1, results.success(index));
+        println!("goal_{}\t{}", index + 1, goal.describe(&entry.banner));
+    }
+    println!("joint_success\t{:.4}", results.joint_success());
+    println!("orbs_left\t{:.2}", stats::mean(&results.orbs_left));
+}
+
+/// Searches for the best stop-loss limits for a campaign, then prints a table
+/// of the best plans with the chance of reaching each goal under them.
+fn optimize_campaign(campaign: &Campaign, options: &Options) {
+    let samples = match options.run_length {
+        RunLength::Samples(samples) => samples,
+        _ => unreachable!("campaigns are checked for --time when parsing"),
+    };
+    let weights = options
+        .campaign
+        .iter()
+        .map(|entry| entry.stage.weight)
+        .collect::<Vec<_>>();
+    let step = options.limit_step.unwrap_or(DEFAULT_LIMIT_STEP);
+    let plans = campaign::optimize_limits(campaign, &weights, step, |campaign| {
+        parallel::run_campaign(campaign, options.seed, samples, options.threads)
+    });
+
+    println!("seed\t{}", options.seed);
+    println!("samples\t{}", samples);
+    for (index, entry) in options.campaign.iter().enumerate() {
+        let goal = entry.goal.as_custom(&entry.banner);
+        println!("goal_{}\t{}", index + 1, goal.describe(&entry.banner));
+    }
+    print!("score\tjoint_success");
+    for index in 1..=options.campaign.len() {
+        print!("\tlimit_{}\tsuccess_{}", index, index);
+    }
+    println!();
+    for plan in &plans {
+        print!("{:.4}\t{:.4}", plan.score, plan.results.joint_success());
+        for (index, limit) in plan.limits.iter().enumerate() {
+            let limit = limit.map_or("none".to_string(), |limit| limit.to_string());
+            print!("\t{}\t{:.4}", limit, plan.results.success(index));
+        }
+        println!();
+    }
+}
+
+/// Prints the results as tab-separated lines so that they are easy to read
+/// from scripts.
+fn print_results<T: Count>(data: &Counter<T>, samples: &str, options: &Options) {
+    println!("samples\t{}", samples);
+    for (name, value) in summary(data, options) {
+        println!("{}\t{}", name, value);
+    }
+    let goal = options.goal.as_custom(&options.banner);
+    println!("goal\t{}", goal.describe(&options.banner));
+}
+
+/// Prints a table with a line of results for the chosen strategy and one of
+/// each of the other kinds, all using the same seed.
+fn compare_strategies(sim: &Sim, options: &Options) {
+    let mut strategies = vec![options.strategy];
+    for strategy in Strategy::comparison_set(&options.goal.as_custom(&options.banner)) {
+        if !strategies.contains(&strategy) {
+            strategies.push(strategy);
+        }
+    }
+
+    for (index, &strategy) in strategies.iter().enumerate() {
+        let mut sim = sim.clone();
+        sim.set_strategy(strategy);
+        let summary = match options.run_length {
+            RunLength::Samples(samples) => summary(
+                &parallel::run(&sim, options.seed, samples, options.threads).orbs,
+                options,
+            ),
+            RunLength::Time(duration) => summary(
+                &parallel::run_for(&sim, options.seed, duration, options.threads).orbs,
+                options,
+            ),
+            RunLength::Exact => summary(&exact::orb_distribution(&sim, 1e-9), options),
+        };
+        if index == 0 {
+            let names = summary.iter().map(|(name, _)| name.as_str());
+            println!("strategy\t{}", names.collect::<Vec<_>>().join("\t"));
+        }
+        let values = summary.iter().map(|(_, value)| value.as_str());
+        println!("{}\t{}", strategy, values.collect::<Vec<_>>().join("\t"));
+    }
+}
+
+/// Finds the optimal policy, then prints either how it compares to the
+/// strategy or the decisions that it makes differently.
+fn optimize(sim: &Sim, options: &Options) {
+    let objective = match options.budget {
+        Some(budget) => Objective::MaximizeSuccess(budget),
+        None => Objective::MinimizeOrbs,
+    };
+    let policy = optimal::solve(sim, objective);
+    if !options.decisions {
+        let (name, precision) = match objective {
+            Objective::MinimizeOrbs => ("mean", 2),
+            Objective::MaximizeSuccess(_) => ("budget_success", 4),
+        };
+        println!("objective\t{}", name);
+        println!("current\t{:.*}", precision, policy.current_value);
+        println!("optimal\t{:.*}", precision, policy.value);
+        println!("savings\t{:.*}", precision, policy.savings());
+        return;
+    }
+
+    if options.budget.is_some() {
+        print!("orbs_left\t");
+    }
+    println!("pity\tcharges\tspark_count\tcopies_needed\tcircle\tpull");
+    for decision in &policy.decisions {
+        if let Some(orbs_left) = decision.orbs_left {
+            print!("{}\t", orbs_left);
+        }
+        let copies_needed = Color::iter()
+            .zip(&decision.copies_needed)
+            .filter(|(_, copies)| !copies.is_empty())
+            .map(|(color, copies)| {
+                let copies = copies.iter().map(u8::to_string).collect::<Vec<_>>();
+                format!("{}{}", color_letter(color), copies.join(","))
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            decision.pity_count,
+            decision.focus_charges,
+            decision.spark_count,
+            copies_needed.join(" "),
+            stones(decision.circle),
+            stones(decision.pulls),
+        );
+    }
+}
+
+/// Writes out a number of stones of each color as a letter per stone.
+fn stones(counts: [u8; 4]) -> String {
+    Color::iter()
+        .zip(&counts)
+        .flat_map(|(color, &count)| std::iter::repeat(color_letter(color)).take(count as usize))
+        .collect()
+}
+
+/// The first letter of a color's name.
+fn color_letter(color: Color) -> char {
+    color.to_string().chars().next().unwrap()
+}
+
+/// The names and values of the statistics that get printed for a run.
+fn summary<T: Count>(data: &Counter<T>, options: &Options) -> Vec<(String, String)> {
+    let mut summary = vec![("mean".to_string(), format!("{:.2}", stats::mean(data)))];
+    let fracs = options
+        .percentiles
+        .iter()
+        .map(|pct| pct / 100.0)
+        .collect::<Vec<_>>();
+    let values = stats::percentiles(data, &fracs);
+    for (pct, value) in options.percentiles.iter().zip(&values) {
+        summary.push((format!("p{}", pct), value.to_string()));
+    }
+    if let Some(income) = options.income {
+        for (pct, &value) in options.percentiles.iter().zip(&values) {
+            let days = income
+                .days_needed(value)
+                .map_or("never".to_string(), |days| days.to_string());
+            summary.push((format!("days_p{}", pct), days));
+        }
+    }
+    if let Some(budget) = options.budget {
+        summary.push((
+            "budget_success".to_string(),
+            format!("{:.4}", stats::cdf(data, budget)),
+        ));
+    }
+    summary
+}
+
+/// Reads the options out of the command line arguments.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        banner: Banner::default(),
+        pool_sizes: PoolSizes::default(),
+        goal: Goal::default(),
+        run_length: RunLength::Samples(10000),
+        percentiles: vec![25.0, 50.0, 75.0, 90.0, 99.0],
+        budget: None,
+        income: None,
+        progress: Progress::default(),
+        strategy: Strategy::default(),
+        compare: false,
+        optimize: false,
+        decisions: false,
+        seed: SmallRng::from_entropy().gen(),
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        campaign: vec![],
+        stage: StageOptions::default(),
+        limit_step: None,
+    };
+    let mut flags = GoalFlags::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .map(String::as_str)
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--template" => {
+                let name = value(arg)?;
+                let template = BannerTemplate::from_name(name).ok_or_else(|| {
+                    let names = BannerTemplate::library()
+                        .iter()
+                        .map(|template| template.name)
+                        .collect::<Vec<_>>();
+                    format!(
+                        "unknown banner template \"{}\", expected one of: {}",
+                        name,
+                        names.join(", ")
+                    )
+                })?;
+                options.banner = template.banner();
+            }
+            "--kind" => options.banner.set_kind(parse_banner_kind(value(arg)?)?),
+            "--rates" => {
+                let rates = value(arg)?;
+                let values = rates
+                    .split('/')
+                    .map(parse_number)
+                    .collect::<Result<Vec<f32>, _>>()?;
+                options.banner.starting_rates = match values[..] {
+                    [focus, fivestar] => Banner::standard_rates(focus, fivestar),
+                    [focus, fivestar, fourstar_focus, fourstar, threestar] => {
+                        [focus, fivestar, fourstar_focus, fourstar, threestar, 0.0]
+                    }
+                    [focus, fivestar, fourstar_focus, fourstar, threestar, fourstar_special] => [
+                        focus,
+                        fivestar,
+                        fourstar_focus,
+                        fourstar,
+                        threestar,
+                        fourstar_special,
+                    ],
+                    _ => return Err(format!("invalid rates \"{}\"", rates)),
+                };
+            }
+            "--focus" => {
+                let sizes = value(arg)?
+                    .split(',')
+                    .map(parse_number)
+                    .collect::<Result<Vec<i8>, _>>()?;
+                if sizes.len() != 4 || sizes.iter().any(|&size| size < 0) {
+                    return Err("--focus needs four non-negative numbers".into());
+                }
+                options.banner.focus_sizes.copy_from_slice(&sizes);
+            }
+            "--no-charges" => options.banner.focus_charges = false,
+            "--fourstar-focus" => {
+                options.banner.fourstar_focus_sizes[parse_color(value(arg)?)? as usize] += 1;
+            }
+            "--spark" => options.banner.spark_threshold = Some(parse_number(value(arg)?)?),
+            "--no-spark" => options.banner.spark_threshold = None,
+            "--pool" => options.pool_sizes = parse_pool_sizes(value(arg)?)?,
+            "--pool-size" => {
+                let (rarity, overrides) = parse_pool_override(value(arg)?)?;
+                options.banner.pool_overrides[rarity] = overrides;
+            }
+            "--preset" => {
+                let name = value(arg)?;
+                let preset = GoalPreset::iter()
+                    .find(|preset| format!("{:?}", preset).eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("unknown goal preset \"{}\"", name))?;
+                options.goal = Goal::Preset(preset, 1);
+                flags.open_groups.clear();
+            }
+            "--count" => flags.preset_count = Some(parse_copies(value(arg)?)?),
+            "--part" => {
+                let part = parse_goal_part(value(arg)?, &options.banner)?;
+                let custom_goal = make_custom(&mut options.goal);
+                let group = flags.open_groups.last().copied().unwrap_or(0);
+                let (_, goals) = custom_goal.group_mut(group).unwrap();
+                goals.push(GoalNode::Part(part));
+            }
+            "--group" => {
+                let kind = parse_goal_kind(value(arg)?)?;
+                let custom_goal = make_custom(&mut options.goal);
+                // The new group is the last one listed, so it gets the next
+                // number.
+                let new_group = custom_goal.group_count();
+                let group = flags.open_groups.last().copied().unwrap_or(0);
+                let (_, goals) = custom_goal.group_mut(group).unwrap();
+                goals.push(GoalNode::Group(GoalGroup {
+                    kind,
+                    goals: vec![],
+                }));
+                flags.open_groups.push(new_group);
+            }
+            "--end-group" => {
+                if flags.open_groups.pop().is_none() {
+                    return Err("--end-group needs a --group to end".into());
+                }
+            }
+            "--all" => flags.all_parts = true,
+            "--at-least" => flags.at_least = Some(parse_number(value(arg)?)?),
+            "--fourstar-merges" => flags.fourstar_merges = true,
+            "--budget" => options.budget = Some(parse_number(value(arg)?)?),
+            "--then" => {
+                finish_banner(&mut options, &flags)?;
+                options.campaign.push(CampaignBanner {
+                    banner: mem::take(&mut options.banner),
+                    goal: mem::take(&mut options.goal),
+                    progress: mem::take(&mut options.progress),
+                    stage: mem::take(&mut options.stage),
+                });
+                flags = GoalFlags::default();
+            }
+            "--income" => options.stage.income = parse_number(value(arg)?)?,
+            "--limit" => options.stage.limit = Some(parse_number(value(arg)?)?),
+            "--weight" => {
+                options.stage.weight = parse_number(value(arg)?)?;
+                if options.stage.weight < 0.0 || !options.stage.weight.is_finite() {
+                    return Err("--weight needs a non-negative number".into());
+                }
+            }
+            "--step" => {
+                let step = parse_number(value(arg)?)?;
+                options.limit_step = Some(step);
+                if step == 0 {
+                    return Err("--step needs a positive number of orbs".into());
+                }
+            }
+            "--profile" => options.income = Some(parse_income_profile(value(arg)?)?),
+            "--saved" => income(&mut options).saved = parse_number(value(arg)?)?,
+            "--daily" => {
+                let daily: f32 = parse_number(value(arg)?)?;
+                if daily < 0.0 || !daily.is_finite() {
+                    return Err("--daily needs a non-negative number of orbs".into());
+                }
+                income(&mut options).daily = daily;
+            }
+            "--monthly-quests" => income(&mut options).monthly_quests = parse_number(value(arg)?)?,
+            "--events" => income(&mut options).events = parse_number(value(arg)?)?,
+            "--purchased" => income(&mut options).purchased = parse_number(value(arg)?)?,
+            "--strategy" => options.strategy = parse_strategy(value(arg)?)?,
+            "--compare" => options.compare = true,
+            "--optimize" => options.optimize = true,
+            "--decisions" => options.decisions = true,
+            "--pity" => options.progress.pity_count = parse_number(value(arg)?)?,
+            "--charges" => options.progress.focus_charges = parse_number(value(arg)?)?,
+            "--spark-count" => options.progress.spark_count = parse_number(value(arg)?)?,
+            "--owned" => {
+                options.progress.copies_owned = value(arg)?
+                    .split(',')
+                    .map(parse_copies)
+                    .collect::<Result<_, _>>()?;
+            }
+            "-n" | "--samples" => {
+                options.run_length = RunLength::Samples(parse_number(value(arg)?)?);
+            }
+            "-t" | "--time" => {
+                let seconds: f64 = parse_number(value(arg)?)?;
+                if seconds <= 0.0 || !seconds.is_finite() {
+                    return Err("--time needs a positive number of seconds".into());
+                }
+                options.run_length = RunLength::Time(Duration::from_secs_f64(seconds));
+            }
+            "--exact" => options.run_length = RunLength::Exact,
+            "-s" | "--seed" => options.seed = parse_number(value(arg)?)?,
+            "-j" | "--threads" => {
+                options.threads = parse_number(value(arg)?)?;
+                if options.threads == 0 {
+                    return Err("--threads needs at least one thread".into());
+                }
+            }
+            "-p" | "--percentiles" => {
+                let mut percentiles = value(arg)?.split(',').map(parse_number).collect::<Result<
+                    Vec<f32>,
+                    _,
+                >>(
+                )?;
+                if percentiles.iter().any(|pct| !(0.0..=100.0).contains(pct)) {
+                    return Err("percentiles must be between 0 and 100".into());
+                }
+                percentiles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                options.percentiles = percentiles;
+            }
+            _ => {
+                // Anything else is part of a permalink, either the whole link
+                // or just its banner= and goal= parameters.
+                let query = arg.splitn(2, '?').last().unwrap();
+                if !query.contains('=') {
+                    return Err(format!("unrecognized argument \"{}\"", arg));
+                }
+                let params = query.split('&').collect::<Vec<_>>();
+                // The version decides how the other parameters are read, so it
+                // has to be found first.
+                let version = params.iter().find_map(|param| param.strip_prefix("v="));
+                for param in params {
+                    parse_query_param(param, version, &mut options)?;
+                }
+            }
+        }
+    }
+
+    finish_banner(&mut options, &flags)?;
+    if !options.campaign.is_empty() {
+        // The last banner of a campaign isn't followed by --then.
+        options.campaign.push(CampaignBanner {
+            banner: options.banner.clone(),
+            goal: options.goal.clone(),
+            progress: options.progress.clone(),
+            stage: mem::take(&mut options.stage),
+        });
+        if options.budget.is_none() {
+            return Err("--then needs a --budget to start the campaign with".into());
+        }
+        if options.compare {
+            return Err("--compare can't be used with --then".into());
+        }
+        if options.decisions {
+            return Err("--decisions can't be used with --then".into());
+        }
+        if options.income.is_some() {
+            return Err("income options can't be used with --then".into());
+        }
+        match options.run_length {
+            RunLength::Exact => return Err("--exact can't be used with --then".into()),
+            RunLength::Time(_) if options.optimize => {
+                return Err("--time can't be used with --optimize and --then".into());
+            }
+            _ => {}
+        }
+    } else {
+        let stage = &options.stage;
+        if stage.income > 0 || stage.limit.is_some() || stage.weight != 1.0 {
+            return Err("--income, --limit and --weight can only be used with --then".into());
+        }
+    }
+
+    if options.limit_step.is_some() && (options.campaign.is_empty() || !options.optimize) {
+        return Err("--step can only be used with --optimize and --then".into());
+    }
+    if options.decisions && !options.optimize {
+        return Err("--decisions can only be used with --optimize".into());
+    }
+
+    Ok(options)
+}
+
+/// Applies the goal options that modify the goal as a whole, now that it's
+/// complete, and fills in the banner's rates for any 4* focus units.
+fn finish_banner(options: &mut Options, flags: &GoalFlags) -> Result<(), String> {
+    match &mut options.goal {
+        Goal::Preset(_, count) => {
+            if let Some(preset_count) = flags.preset_count {
+                *count = preset_count;
+            }
+            if flags.all_parts {
+                return Err("--all can only be used with --part".into());
+            }
+            if flags.at_least.is_some() {
+                return Err("--at-least can only be used with --part".into());
+            }
+            if flags.fourstar_merges {
+                return Err("--fourstar-merges can only be used with --part".into());
+            }
+        }
+        Goal::Custom(custom_goal) => {
+            if flags.preset_count.is_some() {
+                return Err("--count can only be used with --preset".into());
+            }
+            if flags.all_parts && flags.at_least.is_some() {
+                return Err("--all and --at-least can't be used together".into());
+            }
+            if flags.all_parts {
+                custom_goal.kind = GoalKind::All;
+            }
+            if let Some(count) = flags.at_least {
+                custom_goal.kind = GoalKind::AtLeast(count);
+            }
+            if flags.fourstar_merges {
+                custom_goal.fourstar_merges = true;
+            }
+        }
+    }
+
+    let banner = &mut options.banner;
+    if banner.has_fourstar_focus() && banner.starting_rates[Pool::FourstarFocus as usize] == 0.0 {
+        banner.starting_rates = Banner::fourstar_focus_rates();
+    }
+    Ok(())
+}
+
+/// Reads a single `key=value` parameter from a permalink's query string, given
+/// the version of the permalink if it has one.
+fn parse_query_param(
+    param: &str,
+    version: Option<&str>,
+    options: &mut Options,
+) -> Result<(), String> {
+    let (key, value) = match param.splitn(2, '=').collect::<Vec<_>>()[..] {
+        [key, value] => (key, value),
+        _ => return Err(format!("invalid permalink parameter \"{}\"", param)),
+    };
+    match key {
+        "banner" => {
+            let banner = match version {
+                Some("3") => legacy::banner_from_v3(value),
+                _ => Banner::from_query_string(value),
+            };
+            options.banner = banner.ok_or_else(|| format!("invalid banner \"{}\"", value))?;
+        }
+        "pool" => options.pool_sizes = parse_pool_sizes(value)?,
+        "goal" => {
+            let goal = match version {
+                Some("3") => legacy::goal_from_v3(value),
+                _ => Goal::from_query_string(value),
+            };
+            options.goal = goal.ok_or_else(|| format!("invalid goal \"{}\"", value))?;
+        }
+        "progress" => {
+            options.progress = Progress::from_query_string(value)
+                .ok_or_else(|| format!("invalid progress \"{}\"", value))?;
+        }
+        "strategy" => {
+            options.strategy = Strategy::from_query_string(value)
+                .ok_or_else(|| format!("invalid strategy \"{}\"", value))?;
+        }
+        "engine" => match value {
+            "exact" => options.run_length = RunLength::Exact,
+            // The number of samples comes separately, if there is one.
+            "simulation" => {}
+            _ => return Err(format!("invalid engine \"{}\"", value)),
+        },
+        "seed" => options.seed = parse_number(value)?,
+        "samples" => options.run_length = RunLength::Samples(parse_number(value)?),
+        "budget" => options.budget = Some(parse_number(value)?),
+        "income" => {
+            options.income = Some(
+                IncomeProfile::from_query_string(value)
+                    .ok_or_else(|| format!("invalid income \"{}\"", value))?,
+            );
+        }
+        "v" => {
+            if value != "3" && value != PERMALINK_VERSION {
+                return Err("the permalink format has changed, please update your link".into());
+            }
+            // Links from before the pool sizes could be chosen don't say which
+            // ones they used, but it was always the oldest ones.
+            options.pool_sizes = PoolSizes::oldest();
+        }
+        // The command line always runs.
+        "run" => {}
+        _ => return Err(format!("unrecognized argument \"{}\"", param)),
+    }
+    Ok(())
+}
+
+/// Reads a goal part in the form `<copies>:<unit>`, with an optional `:4` at
+/// the end for one of the 4* focus units, in the form `<copies>:<color>:pool`
+/// for a non-focus unit, or in the form `<copies>:any` for any 5* units.
+fn parse_goal_part(s: &str, banner: &Banner) -> Result<GoalPart, String> {
+    let (copies, (color, unit), target) = match s.split(':').collect::<Vec<_>>()[..] {
+        [copies, "any"] => (copies, (Color::Red, None), PartTarget::AnyFivestar),
+        [copies, unit] => (copies, parse_unit(unit, banner)?, PartTarget::Focus),
+        [copies, unit, "4"] => (copies, parse_unit(unit, banner)?, PartTarget::FourstarFocus),
+        [copies, color, "pool"] => (copies, (parse_color(color)?, None), PartTarget::NonFocus),
+        _ => return Err(format!("invalid goal part \"{}\"", s)),
+    };
+    Ok(GoalPart {
+        unit_color: color,
+        num_copies: parse_copies(copies)?,
+        target,
+        unit,
+    })
+}
+
+/// Reads either a color, for some focus unit of that color, or the name of
+/// one of the banner's focus units, for that unit in particular.
+fn parse_unit(s: &str, banner: &Banner) -> Result<(Color, Option<u8>), String> {
+    if let Ok(color) = parse_color(s) {
+        return Ok((color, None));
+    }
+    for color in Color::iter() {
+        for slot in 0..banner.focus_sizes[color as usize].max(0) as usize {
+            if banner
+                .focus_name(color, slot)
+                .map_or(false, |name| name.eq_ignore_ascii_case(s))
+            {
+                return Ok((color, Some(slot as u8)));
+            }
+        }
+    }
+    Err(format!("unknown color or focus unit \"{}\"", s))
+}
+
+/// Reads the kind of a nested goal group.
+fn parse_goal_kind(s: &str) -> Result<GoalKind, String> {
+    match s {
+        "any" => Ok(GoalKind::Any),
+        "all" => Ok(GoalKind::All),
+        _ => s
+            .parse()
+            .map(GoalKind::AtLeast)
+            .map_err(|_| format!("invalid goal group kind \"{}\"", s)),
+    }
+}
+
+/// Switches the goal to an empty custom goal if it is a preset, so that parts
+/// can be added to it.
+fn make_custom(goal: &mut Goal) -> &mut CustomGoal {
+    if let Goal::Preset(..) = goal {
+        *goal = Goal::Custom(CustomGoal {
+            kind: GoalKind::Any,
+            goals: vec![],
+            fourstar_merges: false,
+        });
+    }
+    match goal {
+        Goal::Custom(custom_goal) => custom_goal,
+        Goal::Preset(..) => unreachable!(),
+    }
+}
+
+/// Reads the month of a version of the pool sizes.
+fn parse_pool_sizes(s: &str) -> Result<PoolSizes, String> {
+    PoolSizes::from_date(s).ok_or_else(|| {
+        let dates = PoolSizes::history()
+            .iter()
+            .map(|sizes| sizes.date)
+            .collect::<Vec<_>>();
+        format!(
+            "no pool sizes from \"{}\", choose from {}",
+            s,
+            dates.join(", ")
+        )
+    })
+}
+
+/// Reads an override for the sizes of one rarity's pool, in the form
+/// `<stars>:<R>,<B>,<G>,<C>`, giving the index of the rarity in
+/// `Banner::pool_overrides` along with the overrides.
+fn parse_pool_override(s: &str) -> Result<(usize, [Option<u8>; 4]), String> {
+    let (stars, sizes) = match s.splitn(2, ':').collect::<Vec<_>>()[..] {
+        [stars, sizes] => (stars, sizes),
+        _ => return Err(format!("invalid pool size override \"{}\"", s)),
+    };
+    let rarity = match stars {
+        "5" => 0,
+        "4" => 1,
+        "3" => 2,
+        _ => return Err(format!("there is no {}* pool", stars)),
+    };
+    let sizes = sizes
+        .split(',')
+        .map(|size| match size {
+            "-" => Ok(None),
+            size => parse_number(size).map(Some),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if sizes.len() != 4 {
+        return Err("--pool-size needs a size for each of the four colors".into());
+    }
+    let mut overrides = [None; 4];
+    overrides.copy_from_slice(&sizes);
+    Ok((rarity, overrides))
+}
+
+/// Reads a kind of banner, written in lowercase with dashes between the words.
+fn parse_banner_kind(s: &str) -> Result<BannerKind, String> {
+    use BannerKind::*;
+    Ok(match s {
+        "new-heroes" => NewHeroes,
+        "legendary" => Legendary,
+        "mythic" => Mythic,
+        "remix" => Remix,
+        "hero-fest" => HeroFest,
+        "weekly-revival" => WeeklyRevival,
+        "double-special" => DoubleSpecial,
+        "attuned" => Attuned,
+        "custom" => Custom,
+        _ => return Err(format!("unknown banner kind \"{}\"", s)),
+    })
+}
+
+/// The income profile being adjusted, starting from the free-to-play one if
+/// none has been chosen yet.
+fn income(options: &mut Options) -> &mut IncomeProfile {
+    options
+        .income
+        .get_or_insert_with(IncomeProfile::free_to_play)
+}
+
+/// Reads the name of one of the income profiles.
+fn parse_income_profile(s: &str) -> Result<IncomeProfile, String> {
+    Ok(match s {
+        "f2p" => IncomeProfile::free_to_play(),
+        "paying" => IncomeProfile::paying(),
+        _ => return Err(format!("unknown income profile \"{}\"", s)),
+    })
+}
+
+/// Reads a strategy name, with its setting after a colon if it has one.
+fn parse_strategy(s: &str) -> Result<Strategy, String> {
+    Ok(match s.splitn(2, ':').collect::<Vec<_>>()[..] {
+        ["goal-colors"] => Strategy::GoalColors,
+        ["full-circle"] => Strategy::FullCircle,
+        ["only", color] => Strategy::SingleColor(parse_color(color)?),
+        ["extra-after", summons] => Strategy::ExtraAtHighPity(parse_number(summons)?),
+        ["off-color", color] => Strategy::OffColor(parse_color(color)?),
+        _ => return Err(format!("unknown strategy \"{}\"", s)),
+    })
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    Color::iter()
+        .find(|color| color.to_string().eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("unknown color \"{}\"", s))
+}
+
+/// Reads a number of copies, either as a plain number or as `+<level>` for the
+/// copies it takes to reach that merge level.
+fn parse_copies(s: &str) -> Result<u8, String> {
+    match s.strip_prefix('+') {
+        Some(level) => match parse_number(level)? {
+            level if level <= MAX_MERGE_LEVEL => Ok(copies_for_merge_level(level)),
+            _ => Err(format!(
+                "merge level \"{}\" is above +{}",
+                s, MAX_MERGE_LEVEL
+            )),
+        },
+        None => parse_number(s),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number \"{}\"", s))
+}
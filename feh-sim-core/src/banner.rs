@@ -0,0 +1,280 @@
+use std::fmt;
+
+use strum_macros::EnumIter;
+
+use crate::{Color, Pool, PoolSizes};
+
+/// The kinds of banners that the game runs, which each have their own rules.
+#[derive(
+    Copy, Clone, Default, PartialEq, Eq, Debug, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+pub enum BannerKind {
+    #[default]
+    NewHeroes,
+    Legendary,
+    Mythic,
+    Remix,
+    HeroFest,
+    WeeklyRevival,
+    DoubleSpecial,
+    Attuned,
+    /// Anything else, with no restrictions on the settings.
+    Custom,
+}
+
+impl fmt::Display for BannerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BannerKind::*;
+        let s = match *self {
+            NewHeroes => "New Heroes",
+            Legendary => "Legendary",
+            Mythic => "Mythic",
+            Remix => "Remix",
+            HeroFest => "Hero Fest",
+            WeeklyRevival => "Weekly Revival",
+            DoubleSpecial => "Double Special Heroes",
+            Attuned => "Attuned",
+            Custom => "Custom",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The rules that every banner of a kind follows.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BannerRules {
+    /// The usual starting rates, without a 4* focus unit.
+    pub rates: [f32; 6],
+    /// The number of focus units of each color, if it is always the same.
+    pub focus_sizes: Option<[i8; 4]>,
+    /// Whether summons count towards a spark.
+    pub spark: bool,
+    /// Whether summons can earn focus charges.
+    pub focus_charges: bool,
+    /// Whether the banner can have a 4* focus unit.
+    pub fourstar_focus: bool,
+    /// Whether the banner can have a special 4* rate for its focus units.
+    pub fourstar_special: bool,
+    /// Whether the non-focus pools leave out some of the usual units, so that
+    /// their sizes need to be entered by hand.
+    pub restricted_pools: bool,
+}
+
+impl BannerKind {
+    /// Gives the rules for this kind of banner.
+    pub fn rules(self) -> BannerRules {
+        use BannerKind::*;
+        let rules = BannerRules {
+            rates: Banner::standard_rates(3.0, 3.0),
+            focus_sizes: None,
+            spark: true,
+            focus_charges: true,
+            fourstar_focus: false,
+            fourstar_special: false,
+            restricted_pools: false,
+        };
+        match self {
+            NewHeroes | Custom => BannerRules {
+                fourstar_focus: true,
+                fourstar_special: true,
+                ..rules
+            },
+            // Neither of these has non-focus 5* units, so there's nothing to
+            // earn focus charges from.
+            Legendary => BannerRules {
+                rates: Banner::standard_rates(8.0, 0.0),
+                focus_sizes: Some([3, 3, 3, 3]),
+                focus_charges: false,
+                ..rules
+            },
+            Mythic => BannerRules {
+                rates: Banner::standard_rates(8.0, 0.0),
+                focus_charges: false,
+                ..rules
+            },
+            Remix => BannerRules {
+                restricted_pools: true,
+                ..rules
+            },
+            HeroFest => BannerRules {
+                rates: Banner::standard_rates(5.0, 3.0),
+                ..rules
+            },
+            WeeklyRevival => BannerRules {
+                rates: Banner::standard_rates(4.0, 2.0),
+                spark: false,
+                ..rules
+            },
+            // The lower-rarity breakdown on this banner is different for no
+            // apparent reason.
+            DoubleSpecial => BannerRules {
+                rates: [6.0, 0.0, 0.0, 60.0, 34.0, 0.0],
+                focus_sizes: Some([2, 2, 2, 2]),
+                focus_charges: false,
+                ..rules
+            },
+            Attuned => rules,
+        }
+    }
+}
+
+/// Representation of a summoning focus.
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Banner {
+    pub kind: BannerKind,
+    pub focus_sizes: [i8; 4],
+    /// The chance of a summon coming from each pool before the 5* rates go
+    /// up, in percent and in the same order as `Pool`.
+    pub starting_rates: [f32; 6],
+    pub focus_charges: bool,
+    /// Number of 4* focus units of each color. They are also the first of
+    /// that color's 5* focus units, so they can appear at either rarity.
+    pub fourstar_focus_sizes: [u8; 4],
+    pub spark_threshold: Option<u32>,
+    /// Sizes that replace the usual ones for the non-focus 5*, 4* and 3*
+    /// pools, in that order, for banners with restricted pools or pools that
+    /// haven't been released yet. Colors without an override keep the usual
+    /// size.
+    pub pool_overrides: [[Option<u8>; 4]; 3],
+    /// Names of the focus units of each color, in the same order as the
+    /// units that goals pick by name, so the 4* focus units come first. Units
+    /// without a name, including any past the end of the list, are left
+    /// unnamed.
+    pub focus_names: [Vec<String>; 4],
+}
+
+impl Default for Banner {
+    fn default() -> Self {
+        Banner {
+            kind: BannerKind::NewHeroes,
+            focus_sizes: [1, 1, 1, 1],
+            starting_rates: Banner::standard_rates(3.0, 3.0),
+            focus_charges: true,
+            fourstar_focus_sizes: [0, 0, 0, 0],
+            spark_threshold: Some(40),
+            pool_overrides: [[None; 4]; 3],
+            focus_names: Default::default(),
+        }
+    }
+}
+
+impl Banner {
+    /// Starting rates with the given 5* focus and non-focus rates, where the
+    /// rest is split between 4* and 3* units in the same proportions as on a
+    /// normal banner.
+    pub fn standard_rates(focus: f32, fivestar: f32) -> [f32; 6] {
+        let fivestar_total = focus + fivestar;
+        let fourstar = (100.0 - fivestar_total) * 58.0 / 94.0;
+        let threestar = (100.0 - fivestar_total) * 36.0 / 94.0;
+        [focus, fivestar, 0.0, fourstar, threestar, 0.0]
+    }
+
+    /// Starting rates for a normal banner that also has a 4* focus unit.
+    pub fn fourstar_focus_rates() -> [f32; 6] {
+        [3.0, 3.0, 3.0, 55.0, 36.0, 0.0]
+    }
+
+    /// Starting rates for a normal banner with a special 4* rate for its
+    /// focus units.
+    pub fn fourstar_special_rates() -> [f32; 6] {
+        [3.0, 3.0, 0.0, 55.0, 36.0, 3.0]
+    }
+
+    /// Switches to a different kind of banner, resetting the settings that
+    /// its rules decide.
+    pub fn set_kind(&mut self, kind: BannerKind) {
+        let rules = kind.rules();
+        self.kind = kind;
+        self.starting_rates = rules.rates;
+        if let Some(focus_sizes) = rules.focus_sizes {
+            self.focus_sizes = focus_sizes;
+        }
+        self.spark_threshold = if rules.spark { Some(40) } else { None };
+        self.focus_charges = rules.focus_charges;
+        self.fourstar_focus_sizes = [0, 0, 0, 0];
+    }
+
+    /// The name of the `slot`th focus unit of the given color, if it is one of
+    /// the banner's focus units and has been named.
+    pub fn focus_name(&self, color: Color, slot: usize) -> Option<&str> {
+        if slot >= self.focus_sizes[color as usize].max(0) as usize {
+            return None;
+        }
+        self.focus_names[color as usize]
+            .get(slot)
+            .map(String::as_str)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Checks whether the banner has any 4* focus units.
+    pub fn has_fourstar_focus(&self) -> bool {
+        self.fourstar_focus_sizes.iter().any(|&size| size > 0)
+    }
+
+    /// Checks whether the banner has a special 4* rate for its focus units.
+    pub fn has_fourstar_special(&self) -> bool {
+        self.starting_rates[Pool::FourstarSpecial as usize] > 0.0
+    }
+
+    /// Checks that the banner only uses the features that its kind allows.
+    pub fn follows_kind_rules(&self) -> bool {
+        let rules = self.kind.rules();
+        (rules.spark || self.spark_threshold.is_none())
+            && (rules.focus_charges || !self.focus_charges)
+            && (rules.fourstar_focus || !self.has_fourstar_focus())
+            && (rules.fourstar_special || !self.has_fourstar_special())
+    }
+
+    /// Checks that the starting rates add up to 100%, that there is a chance
+    /// of the focus units appearing, including 4* focus units exactly when
+    /// there are any, and that there are 4* or 3* rates for the 5* rates to
+    /// take their increases from.
+    pub fn has_valid_rates(&self) -> bool {
+        let rates = self.starting_rates;
+        let total: f32 = rates.iter().sum();
+        rates.iter().all(|&rate| rate >= 0.0)
+            && (total - 100.0).abs() < 0.01
+            && rates[Pool::Focus as usize] > 0.0
+            && (rates[Pool::FourstarFocus as usize] > 0.0) == self.has_fourstar_focus()
+            && rates[Pool::Fourstar as usize] + rates[Pool::Threestar as usize] > 0.0
+    }
+
+    /// Applies the banner's pool size overrides to the usual pool sizes.
+    pub fn pool_sizes(&self, mut pool_sizes: PoolSizes) -> PoolSizes {
+        let mut pools = [
+            &mut pool_sizes.fivestar,
+            &mut pool_sizes.fourstar,
+            &mut pool_sizes.threestar,
+        ];
+        for (sizes, overrides) in pools.iter_mut().zip(&self.pool_overrides) {
+            for (size, &size_override) in sizes.iter_mut().zip(overrides) {
+                if let Some(size_override) = size_override {
+                    *size = size_override;
+                }
+            }
+        }
+        pool_sizes
+    }
+
+    /// Checks that every non-focus pool that can be summoned from on this
+    /// banner still has units in it after the overrides.
+    pub fn has_valid_pools(&self, pool_sizes: PoolSizes) -> bool {
+        let pool_sizes = self.pool_sizes(pool_sizes);
+        let has_fivestar_pool = self.starting_rates[Pool::Fivestar as usize] > 0.0;
+        let has_units = |sizes: [u8; 4]| sizes.iter().any(|&size| size > 0);
+        (has_units(pool_sizes.fivestar) || !has_fivestar_pool)
+            && has_units(pool_sizes.fourstar)
+            && has_units(pool_sizes.threestar)
+    }
+
+    /// Parses data from the representation used in query strings to share settings.
+    pub fn from_query_string(s: &str) -> Option<Self> {
+        let data = base64::decode(s).ok()?;
+        bincode::deserialize(&data).ok()
+    }
+
+    /// Converts to the representation used in query strings to share settings.
+    pub fn to_query_string(&self) -> String {
+        base64::encode(&bincode::serialize(self).unwrap())
+    }
+}
@@ -0,0 +1,71 @@
+use std::iter::Sum;
+use std::ops::{AddAssign, Deref, DerefMut, Index, IndexMut};
+
+/// A numeric type that can be used for the values in a `Counter`.
+pub trait Count: Copy + Default + PartialOrd + AddAssign + Sum + Into<f64> + 'static {
+    /// Reference to zero, for returning from out-of-range lookups.
+    const ZERO: &'static Self;
+}
+
+impl Count for u32 {
+    const ZERO: &'static Self = &0;
+}
+
+impl Count for f64 {
+    const ZERO: &'static Self = &0.0;
+}
+
+/// Associative array of u32 -> T with the interface and implementation optimized
+/// for use as a counter for small numbers with a dense distribution. Sampled
+/// data uses the default of u32 counts, and exact distributions use f64
+/// probabilities.
+#[derive(Default, Debug, Clone)]
+pub struct Counter<T = u32> {
+    data: Vec<T>,
+}
+
+impl<T: Count> Index<u32> for Counter<T> {
+    type Output = T;
+
+    /// Infallible. Returns 0 if index is out of range.
+    fn index(&self, index: u32) -> &Self::Output {
+        self.data.get(index as usize).unwrap_or(T::ZERO)
+    }
+}
+
+impl<T: Count> IndexMut<u32> for Counter<T> {
+    /// Infallible. Resizes container if index is out of range.
+    fn index_mut(&mut self, index: u32) -> &mut Self::Output {
+        let index = index as usize;
+        if index >= self.data.len() {
+            self.data.resize(index + 1, T::default());
+        }
+        &mut self.data[index]
+    }
+}
+
+impl<T> Deref for Counter<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> DerefMut for Counter<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<T: Count> Counter<T> {
+    /// Adds all of the counts from `other` into this counter.
+    pub fn merge(&mut self, other: &Counter<T>) {
+        if other.data.len() > self.data.len() {
+            self.data.resize(other.data.len(), T::default());
+        }
+        for (count, &other_count) in self.data.iter_mut().zip(other.data.iter()) {
+            *count += other_count;
+        }
+    }
+}
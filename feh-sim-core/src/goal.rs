@@ -0,0 +1,739 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{banner::Banner, Color, Pool, PoolSizes};
+
+/// Pre-set options for common goals.
+#[derive(Copy, Clone, Debug, EnumIter, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GoalPreset {
+    AnyFocus,
+    AllFocus,
+    RedFocus,
+    AnyRed,
+    RedFourstarFocus,
+    BlueFocus,
+    AnyBlue,
+    BlueFourstarFocus,
+    GreenFocus,
+    AnyGreen,
+    GreenFourstarFocus,
+    ColorlessFocus,
+    AnyColorless,
+    ColorlessFourstarFocus,
+}
+
+impl fmt::Display for GoalPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::goal::GoalPreset::*;
+        let s = match *self {
+            AnyFocus => "Any 5* focus unit",
+            AllFocus => "All focus units",
+            RedFocus => "Specific red 5* focus unit",
+            RedFourstarFocus => "Specific red 4* focus unit",
+            AnyRed => "Any red 5* focus unit",
+            BlueFocus => "Specific blue 5* focus unit",
+            BlueFourstarFocus => "Specific blue 4* focus unit",
+            AnyBlue => "Any blue 5* focus unit",
+            GreenFocus => "Specific green 5* focus unit",
+            GreenFourstarFocus => "Specific green 4* focus unit",
+            AnyGreen => "Any green 5* focus unit",
+            ColorlessFocus => "Specific colorless 5* focus unit",
+            AnyColorless => "Any colorless 5* focus unit",
+            ColorlessFourstarFocus => "Specific colorless 4* focus unit",
+        };
+        f.write_str(s)
+    }
+}
+
+impl TryFrom<u8> for GoalPreset {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        for variant in GoalPreset::iter() {
+            if variant as usize == value as usize {
+                return Ok(variant);
+            }
+        }
+        Err(())
+    }
+}
+
+impl GoalPreset {
+    /// Determines whether or not the selected preset is a goal that it is
+    /// possible to achieve on the banner.
+    pub fn is_available(self, banner: &Banner) -> bool {
+        use GoalPreset::*;
+        match self {
+            AnyFocus | AllFocus => banner.focus_sizes.iter().any(|&x| x > 0),
+            RedFocus | AnyRed => banner.focus_sizes[0] > 0,
+            BlueFocus | AnyBlue => banner.focus_sizes[1] > 0,
+            GreenFocus | AnyGreen => banner.focus_sizes[2] > 0,
+            ColorlessFocus | AnyColorless => banner.focus_sizes[3] > 0,
+            RedFourstarFocus => banner.fourstar_focus_sizes[0] > 0 && banner.focus_sizes[0] > 0,
+            BlueFourstarFocus => banner.fourstar_focus_sizes[1] > 0 && banner.focus_sizes[1] > 0,
+            GreenFourstarFocus => banner.fourstar_focus_sizes[2] > 0 && banner.focus_sizes[2] > 0,
+            ColorlessFourstarFocus => {
+                banner.fourstar_focus_sizes[3] > 0 && banner.focus_sizes[3] > 0
+            }
+        }
+    }
+}
+
+/// The highest merge level that a unit can reach.
+pub const MAX_MERGE_LEVEL: u8 = 10;
+
+/// The number of copies of a unit that it takes to reach the given merge
+/// level, where +0 is a single copy.
+pub fn copies_for_merge_level(merge_level: u8) -> u8 {
+    merge_level.min(MAX_MERGE_LEVEL) + 1
+}
+
+/// The merge level that the given number of copies of a unit add up to, or
+/// `None` for no copies at all.
+pub fn merge_level(copies: u8) -> Option<u8> {
+    copies.checked_sub(1)
+}
+
+/// Whether the given goal is to achieve all of the goal parts, just a single
+/// one, or some number in between.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GoalKind {
+    Any,
+    All,
+    AtLeast(u8),
+}
+
+impl GoalKind {
+    /// The number of goal parts out of `parts` that need to be achieved.
+    pub fn parts_needed(self, parts: usize) -> usize {
+        match self {
+            GoalKind::Any => 1,
+            GoalKind::All => parts,
+            GoalKind::AtLeast(count) => count as usize,
+        }
+    }
+
+    /// Lowers an `AtLeast` count so that it doesn't ask for more than
+    /// `parts` goal parts.
+    pub fn fit(&mut self, parts: usize) {
+        if let GoalKind::AtLeast(count) = self {
+            *count = (*count).min(parts as u8);
+        }
+    }
+}
+
+/// Which units can count towards a goal part.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartTarget {
+    /// A specific 5* focus unit of the part's color.
+    #[default]
+    Focus,
+    /// A specific 4* focus unit of the part's color, so that its 4* copies
+    /// count too.
+    FourstarFocus,
+    /// A specific unit of the part's color from the non-focus 5* pool.
+    NonFocus,
+    /// Every 5* unit, focus or not and of any color, so the part's color is
+    /// ignored.
+    AnyFivestar,
+}
+
+/// A single unit that the goal is trying to obtain.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct GoalPart {
+    pub unit_color: Color,
+    /// The number of copies to get, which is one more than the merge level
+    /// to reach. Parts that count any 5* unit count units instead.
+    pub num_copies: u8,
+    /// Which units count for the part. Each part of the same color and
+    /// target is a different unit.
+    pub target: PartTarget,
+    /// Which of the color's focus units the part is, as a position in the
+    /// banner's list of focus unit names, if it was picked by name.
+    pub unit: Option<u8>,
+}
+
+impl GoalPart {
+    /// Checks whether the units that the part counts can appear on the
+    /// banner at all, with the given pool sizes.
+    pub fn can_appear(&self, banner: &Banner, pool_sizes: PoolSizes) -> bool {
+        let color = self.unit_color as usize;
+        match self.target {
+            PartTarget::Focus | PartTarget::FourstarFocus => banner.focus_sizes[color] > 0,
+            PartTarget::NonFocus => {
+                banner.starting_rates[Pool::Fivestar as usize] > 0.0
+                    && banner.pool_sizes(pool_sizes).fivestar[color] > 0
+            }
+            PartTarget::AnyFivestar => true,
+        }
+    }
+
+    /// The name of the unit, if it was picked by name and the banner has one
+    /// for it.
+    pub fn unit_name<'a>(&self, banner: &'a Banner) -> Option<&'a str> {
+        banner.focus_name(self.unit_color, self.unit? as usize)
+    }
+
+    /// A short description of the part for labeling it, such as "Alfonse
+    /// +10" for a unit with a name.
+    pub fn describe(&self, banner: &Banner) -> String {
+        let level = merge_level(self.num_copies).unwrap_or(0);
+        if let Some(name) = self.unit_name(banner) {
+            return format!("{} +{}", name, level);
+        }
+        let color = self.unit_color.to_string().to_lowercase();
+        match self.target {
+            PartTarget::Focus => format!("{} 5* focus unit +{}", color, level),
+            PartTarget::FourstarFocus => format!("{} 4* focus unit +{}", color, level),
+            PartTarget::NonFocus => format!("{} non-focus 5* unit +{}", color, level),
+            PartTarget::AnyFivestar => format!("{} 5* units", self.num_copies),
+        }
+    }
+}
+
+/// One entry in a custom goal, which is either a unit or a nested group of
+/// entries.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GoalNode {
+    Part(GoalPart),
+    Group(GoalGroup),
+}
+
+/// A group of entries within a custom goal, which counts as achieved once
+/// enough of its own entries are.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GoalGroup {
+    pub kind: GoalKind,
+    pub goals: Vec<GoalNode>,
+}
+
+/// The flexible representation of a goal
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CustomGoal {
+    pub kind: GoalKind,
+    pub goals: Vec<GoalNode>,
+    /// Whether 4* copies of the 5* focus units from the special 4* rate count
+    /// towards the goal, as they can be merged into the 5* unit.
+    pub fourstar_merges: bool,
+}
+
+impl CustomGoal {
+    /// Every unit in the goal, including the ones in nested groups, in the
+    /// order that they are listed. Progress refers to the parts by their
+    /// position in this list.
+    pub fn parts(&self) -> Vec<GoalPart> {
+        fn add_parts(nodes: &[GoalNode], parts: &mut Vec<GoalPart>) {
+            for node in nodes {
+                match node {
+                    GoalNode::Part(part) => parts.push(*part),
+                    GoalNode::Group(group) => add_parts(&group.goals, parts),
+                }
+            }
+        }
+        let mut parts = vec![];
+        add_parts(&self.goals, &mut parts);
+        parts
+    }
+
+    /// The `index`th unit in the goal, counted in the same order as `parts`.
+    pub fn part_mut(&mut self, index: usize) -> Option<&mut GoalPart> {
+        fn find<'a>(nodes: &'a mut [GoalNode], index: &mut usize) -> Option<&'a mut GoalPart> {
+            for node in nodes {
+                match node {
+                    GoalNode::Part(part) => {
+                        if *index == 0 {
+                            return Some(part);
+                        }
+                        *index -= 1;
+                    }
+                    GoalNode::Group(group) => {
+                        if let Some(part) = find(&mut group.goals, index) {
+                            return Some(part);
+                        }
+                    }
+                }
+            }
+            None
+        }
+        find(&mut self.goals, &mut { index })
+    }
+
+    /// Removes the `index`th unit in the goal, counted in the same order as
+    /// `parts`, along with any group that it leaves empty.
+    pub fn remove_part(&mut self, index: usize) {
+        fn remove(nodes: &mut Vec<GoalNode>, index: &mut usize) -> bool {
+            for position in 0..nodes.len() {
+                match &mut nodes[position] {
+                    GoalNode::Part(_) => {
+                        if *index == 0 {
+                            nodes.remove(position);
+                            return true;
+                        }
+                        *index -= 1;
+                    }
+                    GoalNode::Group(group) => {
+                        if remove(&mut group.goals, index) {
+                            if group.goals.is_empty() {
+                                nodes.remove(position);
+                            } else {
+                                group.kind.fit(group.goals.len());
+                            }
+                            return true;
+                        }
+                    }
+                }
+            }
+            false
+        }
+        remove(&mut self.goals, &mut { index });
+        self.kind.fit(self.goals.len());
+    }
+
+    /// Adds an entry to the end of the `group`th group, numbered as in
+    /// `group_mut`. Returns the position in `parts` of the first unit in the
+    /// new entry, or `None` if there is no such group.
+    pub fn add_node(&mut self, group: usize, node: GoalNode) -> Option<usize> {
+        fn add(
+            nodes: &mut Vec<GoalNode>,
+            target: usize,
+            groups: &mut usize,
+            parts: &mut usize,
+            node: &mut Option<GoalNode>,
+        ) -> Option<usize> {
+            let group = *groups;
+            *groups += 1;
+            for existing in nodes.iter_mut() {
+                match existing {
+                    GoalNode::Part(_) => *parts += 1,
+                    GoalNode::Group(nested) => {
+                        if let Some(index) = add(&mut nested.goals, target, groups, parts, node) {
+                            return Some(index);
+                        }
+                    }
+                }
+            }
+            if group == target {
+                nodes.extend(node.take());
+                Some(*parts)
+            } else {
+                None
+            }
+        }
+        add(&mut self.goals, group, &mut 0, &mut 0, &mut Some(node))
+    }
+
+    /// A short description of the whole goal, such as "Alfonse +10 and
+    /// (Sharena +0 or Anna +0)", for labeling the results.
+    pub fn describe(&self, banner: &Banner) -> String {
+        fn describe_group(kind: GoalKind, nodes: &[GoalNode], banner: &Banner) -> String {
+            let entries = nodes
+                .iter()
+                .map(|node| match node {
+                    GoalNode::Part(part) => part.describe(banner),
+                    GoalNode::Group(group) if group.goals.len() > 1 => {
+                        format!("({})", describe_group(group.kind, &group.goals, banner))
+                    }
+                    GoalNode::Group(group) => describe_group(group.kind, &group.goals, banner),
+                })
+                .collect::<Vec<_>>();
+            if entries.len() == 1 {
+                return entries[0].clone();
+            }
+            match kind {
+                GoalKind::Any => entries.join(" or "),
+                GoalKind::All => entries.join(" and "),
+                GoalKind::AtLeast(count) => format!("at least {} of {}", count, entries.join(", ")),
+            }
+        }
+        describe_group(self.kind, &self.goals, banner)
+    }
+
+    /// The number of groups in the goal, counting the whole goal as one.
+    pub fn group_count(&self) -> usize {
+        fn count(nodes: &[GoalNode]) -> usize {
+            nodes
+                .iter()
+                .map(|node| match node {
+                    GoalNode::Part(_) => 0,
+                    GoalNode::Group(group) => 1 + count(&group.goals),
+                })
+                .sum()
+        }
+        1 + count(&self.goals)
+    }
+
+    /// The kind and entries of the `group`th group, where the whole goal is
+    /// group 0 and the nested groups are numbered in the order that they are
+    /// listed.
+    pub fn group_mut(&mut self, group: usize) -> Option<(&mut GoalKind, &mut Vec<GoalNode>)> {
+        fn find<'a>(nodes: &'a mut [GoalNode], group: &mut usize) -> Option<&'a mut GoalGroup> {
+            for node in nodes {
+                if let GoalNode::Group(nested) = node {
+                    *group -= 1;
+                    if *group == 0 {
+                        return Some(nested);
+                    }
+                    if let Some(found) = find(&mut nested.goals, group) {
+                        return Some(found);
+                    }
+                }
+            }
+            None
+        }
+        if group == 0 {
+            return Some((&mut self.kind, &mut self.goals));
+        }
+        find(&mut self.goals, &mut { group }).map(|nested| (&mut nested.kind, &mut nested.goals))
+    }
+}
+
+/// Checks that a group and every group nested in it have entries, and don't
+/// need more of them than they have.
+fn is_well_formed(kind: GoalKind, nodes: &[GoalNode]) -> bool {
+    (1..=nodes.len()).contains(&kind.parts_needed(nodes.len()))
+        && nodes.iter().all(|node| match node {
+            GoalNode::Part(_) => true,
+            GoalNode::Group(group) => is_well_formed(group.kind, &group.goals),
+        })
+}
+
+/// Checks that enough of a group's entries can be achieved on the banner for
+/// the group to be achieved, counting nested groups by the same rule. A part
+/// whose units never appear can't be achieved, so an "all" group can't have
+/// any of those, and an "at least K" group needs K entries that can be.
+fn can_be_reached(
+    kind: GoalKind,
+    nodes: &[GoalNode],
+    banner: &Banner,
+    pool_sizes: PoolSizes,
+) -> bool {
+    let reachable = nodes
+        .iter()
+        .filter(|node| match node {
+            GoalNode::Part(part) => part.can_appear(banner, pool_sizes),
+            GoalNode::Group(group) => can_be_reached(group.kind, &group.goals, banner, pool_sizes),
+        })
+        .count();
+    reachable >= kind.parts_needed(nodes.len())
+}
+
+/// Checks that the parts picked by name are focus units of the banner, that
+/// each of those units is picked by only one part, and that only 4* focus
+/// units are picked for 4* targets.
+fn picks_units_correctly(parts: &[GoalPart], banner: &Banner) -> bool {
+    let mut picked = vec![];
+    for part in parts {
+        let slot = match part.unit {
+            Some(slot) => slot,
+            None => continue,
+        };
+        let color = part.unit_color as usize;
+        let fits = match part.target {
+            PartTarget::Focus => (slot as usize) < banner.focus_sizes[color].max(0) as usize,
+            PartTarget::FourstarFocus => slot < banner.fourstar_focus_sizes[color],
+            PartTarget::NonFocus | PartTarget::AnyFivestar => false,
+        };
+        if !fits || picked.contains(&(color, slot)) {
+            return false;
+        }
+        picked.push((color, slot));
+    }
+    true
+}
+
+/// The goal of a summoning session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Goal {
+    Custom(CustomGoal),
+    /// A preset, along with the number of copies to get of each of its units.
+    Preset(GoalPreset, u8),
+}
+
+impl Default for Goal {
+    fn default() -> Self {
+        Goal::Preset(GoalPreset::AnyFocus, 1)
+    }
+}
+
+impl Goal {
+    /// Convert the current preset into a custom goal or retreive the current
+    /// custom goal.
+    pub fn as_custom(&self, banner: &Banner) -> CustomGoal {
+        use crate::goal::GoalKind::*;
+        use crate::goal::GoalPreset::*;
+        use crate::Color::*;
+
+        let (preset, count) = match self {
+            Goal::Preset(preset, count) => (*preset, *count),
+            Goal::Custom(custom) => return custom.clone(),
+        };
+
+        let count = count.max(1);
+
+        let kind = match preset {
+            AllFocus => All,
+            // Every other preset is either Any* or has only one target
+            _ => Any,
+        };
+        let mut custom_goal = CustomGoal {
+            kind,
+            goals: vec![],
+            fourstar_merges: false,
+        };
+
+        let mut add_color_goal = |color: Color, four_star: bool, unit: Option<u8>| {
+            custom_goal.goals.push(GoalNode::Part(GoalPart {
+                unit_color: color,
+                num_copies: count,
+                target: if four_star {
+                    PartTarget::FourstarFocus
+                } else {
+                    PartTarget::Focus
+                },
+                unit,
+            }));
+        };
+        // Add an individual GoalPart for each focus unit that matches the
+        // conditions of the overall goal. The presets that go through every
+        // focus unit pick each one by its position, so that they can be
+        // labeled with their names.
+        match preset {
+            AllFocus | AnyFocus => {
+                for idx in 0..banner.focus_sizes.len() {
+                    for slot in 0..banner.focus_sizes[idx] {
+                        add_color_goal(
+                            Color::try_from(idx as u8).unwrap(),
+                            false,
+                            Some(slot as u8),
+                        );
+                    }
+                }
+            }
+            RedFocus => add_color_goal(Red, false, None),
+            BlueFocus => add_color_goal(Blue, false, None),
+            GreenFocus => add_color_goal(Green, false, None),
+            ColorlessFocus => add_color_goal(Colorless, false, None),
+            AnyRed => {
+                for slot in 0..banner.focus_sizes[0] {
+                    add_color_goal(Red, false, Some(slot as u8))
+                }
+            }
+            AnyBlue => {
+                for slot in 0..banner.focus_sizes[1] {
+                    add_color_goal(Blue, false, Some(slot as u8))
+                }
+            }
+            AnyGreen => {
+                for slot in 0..banner.focus_sizes[2] {
+                    add_color_goal(Green, false, Some(slot as u8))
+                }
+            }
+            AnyColorless => {
+                for slot in 0..banner.focus_sizes[3] {
+                    add_color_goal(Colorless, false, Some(slot as u8))
+                }
+            }
+            RedFourstarFocus => add_color_goal(Red, true, None),
+            BlueFourstarFocus => add_color_goal(Blue, true, None),
+            GreenFourstarFocus => add_color_goal(Green, true, None),
+            ColorlessFourstarFocus => add_color_goal(Colorless, true, None),
+        }
+
+        custom_goal
+    }
+
+    /// Checks whether or not the goal is possible on the given banner, with
+    /// the given pool sizes.
+    pub fn is_available(&self, banner: &Banner, pool_sizes: PoolSizes) -> bool {
+        match self {
+            Goal::Custom(custom_goal) => {
+                let parts = custom_goal.parts();
+                // Every part of the same color and target needs a different
+                // unit, and the 4* focus units are also among the color's 5*
+                // focus units.
+                let mut focus_parts = [0; 4];
+                let mut fourstar_parts = [0; 4];
+                let mut nonfocus_parts = [0; 4];
+                for part in &parts {
+                    let color = part.unit_color as usize;
+                    match part.target {
+                        PartTarget::Focus => focus_parts[color] += 1,
+                        PartTarget::FourstarFocus => {
+                            focus_parts[color] += 1;
+                            fourstar_parts[color] += 1;
+                        }
+                        PartTarget::NonFocus => nonfocus_parts[color] += 1,
+                        PartTarget::AnyFivestar => {}
+                    }
+                }
+                let fivestar_pool = banner.pool_sizes(pool_sizes).fivestar;
+                let enough_units = (0..4).all(|color| {
+                    focus_parts[color] <= banner.focus_sizes[color].max(0) as usize
+                        && fourstar_parts[color] <= banner.fourstar_focus_sizes[color] as usize
+                        && nonfocus_parts[color] <= fivestar_pool[color] as usize
+                });
+                is_well_formed(custom_goal.kind, &custom_goal.goals)
+                    && can_be_reached(custom_goal.kind, &custom_goal.goals, banner, pool_sizes)
+                    && picks_units_correctly(&parts, banner)
+                    && enough_units
+            }
+            Goal::Preset(preset, _) => preset.is_available(banner),
+        }
+    }
+
+    /// Parses data from the representation used in query strings to share settings.
+    pub fn from_query_string(s: &str) -> Option<Self> {
+        let data = base64::decode(s).ok()?;
+        bincode::deserialize(&data).ok()
+    }
+
+    /// Converts to the representation used in query strings to share settings.
+    pub fn to_query_string(&self) -> String {
+        base64::encode(&bincode::serialize(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use strum::IntoEnumIterator;
+
+    use crate::banner::BannerKind;
+
+    fn part(unit_color: Color, target: PartTarget) -> GoalNode {
+        GoalNode::Part(GoalPart {
+            unit_color,
+            num_copies: 1,
+            target,
+            unit: None,
+        })
+    }
+
+    fn custom(kind: GoalKind, goals: Vec<GoalNode>) -> Goal {
+        Goal::Custom(CustomGoal {
+            kind,
+            goals,
+            fourstar_merges: false,
+        })
+    }
+
+    #[test]
+    fn goals_that_need_missing_units_are_unavailable() {
+        let mut banner = Banner::default();
+        banner.set_kind(BannerKind::Legendary);
+        let focus = part(Color::Red, PartTarget::Focus);
+        let non_focus = part(Color::Red, PartTarget::NonFocus);
+
+        // Legendary banners have no non-focus 5* units.
+        assert!(
+            custom(GoalKind::Any, vec![focus.clone(), non_focus.clone()])
+                .is_available(&banner, PoolSizes::default())
+        );
+        assert!(
+            !custom(GoalKind::All, vec![focus.clone(), non_focus.clone()])
+                .is_available(&banner, PoolSizes::default())
+        );
+        assert!(!custom(
+            GoalKind::AtLeast(2),
+            vec![focus.clone(), non_focus.clone(), non_focus.clone()],
+        )
+        .is_available(&banner, PoolSizes::default()));
+
+        // The same goes for parts inside of groups.
+        let group = GoalNode::Group(GoalGroup {
+            kind: GoalKind::All,
+            goals: vec![focus.clone(), non_focus.clone()],
+        });
+        assert!(!custom(GoalKind::All, vec![focus.clone(), group.clone()])
+            .is_available(&banner, PoolSizes::default()));
+        assert!(
+            custom(GoalKind::Any, vec![focus, group]).is_available(&banner, PoolSizes::default())
+        );
+    }
+
+    #[test]
+    fn goals_need_a_unit_for_each_part() {
+        let banner = Banner {
+            focus_sizes: [1, 2, 1, 1],
+            pool_overrides: [[Some(1), None, None, None], [None; 4], [None; 4]],
+            ..Banner::default()
+        };
+        let pool_sizes = PoolSizes::default();
+        let available = |color, target, count| {
+            custom(GoalKind::All, vec![part(color, target); count])
+                .is_available(&banner, pool_sizes)
+        };
+        assert!(available(Color::Red, PartTarget::Focus, 1));
+        assert!(!available(Color::Red, PartTarget::Focus, 2));
+        assert!(available(Color::Blue, PartTarget::Focus, 2));
+        assert!(!available(Color::Blue, PartTarget::Focus, 3));
+        assert!(available(Color::Red, PartTarget::NonFocus, 1));
+        assert!(!available(Color::Red, PartTarget::NonFocus, 2));
+        assert!(available(Color::Red, PartTarget::AnyFivestar, 3));
+
+        // 4* focus units are also 5* focus units of their color.
+        let banner = Banner {
+            fourstar_focus_sizes: [1, 0, 0, 0],
+            starting_rates: Banner::fourstar_focus_rates(),
+            ..banner
+        };
+        let goal = custom(
+            GoalKind::All,
+            vec![
+                part(Color::Red, PartTarget::FourstarFocus),
+                part(Color::Red, PartTarget::Focus),
+            ],
+        );
+        assert!(!goal.is_available(&banner, pool_sizes));
+    }
+
+    #[test]
+    fn empty_pools_are_unavailable() {
+        let mut banner = Banner::default();
+        let non_focus = custom(GoalKind::All, vec![part(Color::Blue, PartTarget::NonFocus)]);
+        assert!(non_focus.is_available(&banner, PoolSizes::default()));
+        banner.pool_overrides[0][Color::Blue as usize] = Some(0);
+        assert!(!non_focus.is_available(&banner, PoolSizes::default()));
+
+        banner.focus_sizes[Color::Green as usize] = 0;
+        assert!(
+            !custom(GoalKind::All, vec![part(Color::Green, PartTarget::Focus)])
+                .is_available(&banner, PoolSizes::default())
+        );
+        assert!(
+            !Goal::Preset(GoalPreset::GreenFocus, 1).is_available(&banner, PoolSizes::default())
+        );
+    }
+
+    #[test]
+    fn merge_levels_convert_to_copies() {
+        assert_eq!(copies_for_merge_level(0), 1);
+        assert_eq!(copies_for_merge_level(MAX_MERGE_LEVEL), 11);
+        assert_eq!(copies_for_merge_level(15), 11);
+        assert_eq!(merge_level(0), None);
+        assert_eq!(merge_level(copies_for_merge_level(4)), Some(4));
+    }
+
+    #[test]
+    fn every_preset_keeps_its_copy_count() {
+        let banner = Banner {
+            fourstar_focus_sizes: [1, 1, 1, 1],
+            ..Banner::default()
+        };
+        for preset in GoalPreset::iter() {
+            let parts = Goal::Preset(preset, 3).as_custom(&banner).parts();
+            assert!(!parts.is_empty());
+            assert!(
+                parts.iter().all(|part| part.num_copies == 3),
+                "{:?}",
+                preset
+            );
+        }
+    }
+}
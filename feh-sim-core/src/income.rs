@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a summoner's orbs come from over time, for turning a number of orbs
+/// into the number of days it takes to save them up. Monthly amounts are
+/// spread out evenly over the month, so the results are averages rather than
+/// a schedule of particular days.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct IncomeProfile {
+    /// Orbs already saved up.
+    pub saved: u32,
+    /// Orbs from login bonuses and daily rewards each day.
+    pub daily: f32,
+    /// Orbs from the monthly quests each month.
+    pub monthly_quests: u32,
+    /// Orbs from events, new maps and other one-off rewards in a typical
+    /// month.
+    pub events: u32,
+    /// Orbs bought in a typical month.
+    pub purchased: u32,
+}
+
+impl Default for IncomeProfile {
+    fn default() -> Self {
+        IncomeProfile::free_to_play()
+    }
+}
+
+impl IncomeProfile {
+    /// A rough estimate of the income of a player who doesn't buy orbs.
+    pub fn free_to_play() -> Self {
+        IncomeProfile {
+            saved: 0,
+            daily: 1.0,
+            monthly_quests: 15,
+            events: 120,
+            purchased: 0,
+        }
+    }
+
+    /// A rough estimate of the income of a player who buys a modest number of
+    /// orbs every month.
+    pub fn paying() -> Self {
+        IncomeProfile {
+            purchased: 140,
+            ..IncomeProfile::free_to_play()
+        }
+    }
+
+    /// The average number of orbs gained per day.
+    pub fn orbs_per_day(&self) -> f64 {
+        let monthly = self.monthly_quests + self.events + self.purchased;
+        self.daily as f64 + monthly as f64 * 12.0 / 365.0
+    }
+
+    /// The number of days of saving until there are `orbs` orbs, counting the
+    /// ones already saved, or `None` if there is no income to save up with.
+    pub fn days_needed(&self, orbs: u32) -> Option<u32> {
+        if orbs <= self.saved {
+            return Some(0);
+        }
+        let per_day = self.orbs_per_day();
+        if per_day <= 0.0 {
+            return None;
+        }
+        Some(((orbs - self.saved) as f64 / per_day).ceil() as u32)
+    }
+
+    /// Parses data from the representation used in query strings to share settings.
+    pub fn from_query_string(s: &str) -> Option<Self> {
+        let data = base64::decode(s).ok()?;
+        bincode::deserialize(&data).ok()
+    }
+
+    /// Converts to the representation used in query strings to share settings.
+    pub fn to_query_string(&self) -> String {
+        base64::encode(&bincode::serialize(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_needed_counts_the_saved_orbs() {
+        let income = IncomeProfile {
+            saved: 50,
+            ..IncomeProfile::free_to_play()
+        };
+        assert_eq!(income.days_needed(40), Some(0));
+        // 1 orb a day plus 135 a month comes to about 5.44 a day.
+        assert_eq!(income.days_needed(100), Some(10));
+        assert_eq!(IncomeProfile::free_to_play().days_needed(100), Some(19));
+        assert!(
+            IncomeProfile::paying().days_needed(1000)
+                < IncomeProfile::free_to_play().days_needed(1000)
+        );
+    }
+
+    #[test]
+    fn no_income_never_gets_there() {
+        let income = IncomeProfile {
+            saved: 20,
+            daily: 0.0,
+            monthly_quests: 0,
+            events: 0,
+            purchased: 0,
+        };
+        assert_eq!(income.days_needed(20), Some(0));
+        assert_eq!(income.days_needed(21), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Readers for the settings in permalinks from older versions of the
+//! simulator, which turn them into the current settings so that old links
+//! keep working.
+
+use serde::Deserialize;
+
+use crate::banner::{Banner, BannerKind};
+use crate::goal::{CustomGoal, Goal, GoalKind, GoalNode, GoalPart, GoalPreset, PartTarget};
+use crate::Color;
+
+/// A banner as it was stored in version 3 permalinks.
+#[derive(Deserialize)]
+struct BannerV3 {
+    focus_sizes: [i8; 4],
+    starting_rates: (u8, u8),
+    focus_charges: bool,
+    fourstar_focus: Option<Color>,
+}
+
+#[derive(Deserialize)]
+enum GoalKindV3 {
+    Any,
+    All,
+}
+
+#[derive(Deserialize)]
+struct GoalPartV3 {
+    unit_color: Color,
+    num_copies: u8,
+    four_star: bool,
+}
+
+#[derive(Deserialize)]
+struct CustomGoalV3 {
+    kind: GoalKindV3,
+    goals: Vec<GoalPartV3>,
+}
+
+/// A goal as it was stored in version 3 permalinks.
+#[derive(Deserialize)]
+enum GoalV3 {
+    Custom(CustomGoalV3),
+    Preset(GoalPreset, u8),
+}
+
+/// Parses a banner from a version 3 permalink. Banners back then had no
+/// sparks, no kinds and at most one 4* focus unit, so they become custom
+/// banners with the same rates.
+pub fn banner_from_v3(s: &str) -> Option<Banner> {
+    let data = base64::decode(s).ok()?;
+    let old: BannerV3 = bincode::deserialize(&data).ok()?;
+    let (focus, fivestar) = old.starting_rates;
+    let mut banner = Banner {
+        kind: BannerKind::Custom,
+        focus_sizes: old.focus_sizes,
+        starting_rates: Banner::standard_rates(focus as f32, fivestar as f32),
+        focus_charges: old.focus_charges,
+        spark_threshold: None,
+        ..Banner::default()
+    };
+    if let Some(color) = old.fourstar_focus {
+        banner.fourstar_focus_sizes[color as usize] = 1;
+        banner.starting_rates = Banner::fourstar_focus_rates();
+    } else if (focus, fivestar) == (6, 0) {
+        banner.starting_rates = BannerKind::DoubleSpecial.rules().rates;
+    }
+    Some(banner)
+}
+
+/// Parses a goal from a version 3 permalink.
+pub fn goal_from_v3(s: &str) -> Option<Goal> {
+    let data = base64::decode(s).ok()?;
+    let old: GoalV3 = bincode::deserialize(&data).ok()?;
+    Some(match old {
+        GoalV3::Preset(preset, count) => Goal::Preset(preset, count),
+        GoalV3::Custom(custom) => Goal::Custom(CustomGoal {
+            kind: match custom.kind {
+                GoalKindV3::Any => GoalKind::Any,
+                GoalKindV3::All => GoalKind::All,
+            },
+            goals: custom
+                .goals
+                .into_iter()
+                .map(|part| {
+                    GoalNode::Part(GoalPart {
+                        unit_color: part.unit_color,
+                        num_copies: part.num_copies,
+                        target: if part.four_star {
+                            PartTarget::FourstarFocus
+                        } else {
+                            PartTarget::Focus
+                        },
+                        unit: None,
+                    })
+                })
+                .collect(),
+            fourstar_merges: false,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::PoolSizes;
+
+    #[test]
+    fn reads_v3_settings() {
+        // Four focus units, 3%/3% rates, focus charges and a red 4* focus unit.
+        let banner = banner_from_v3("AQEBAQMDAQEAAAAA").unwrap();
+        assert_eq!(banner.kind, BannerKind::Custom);
+        assert_eq!(banner.focus_sizes, [1, 1, 1, 1]);
+        assert_eq!(banner.starting_rates, Banner::fourstar_focus_rates());
+        assert_eq!(banner.fourstar_focus_sizes, [1, 0, 0, 0]);
+        assert_eq!(banner.spark_threshold, None);
+        assert!(banner.focus_charges);
+        assert!(banner.follows_kind_rules() && banner.has_valid_rates());
+
+        // All of +3 on the red 4* focus unit.
+        let goal = goal_from_v3("AAAAAAEAAAABAAAAAAAAAAAAAAAEAQ==").unwrap();
+        assert!(goal.is_available(&banner, PoolSizes::default()));
+        let goal = goal.as_custom(&banner);
+        assert_eq!(goal.kind, GoalKind::All);
+        assert_eq!(goal.parts().len(), 1);
+        let part = goal.parts()[0];
+        assert_eq!(part.unit_color, Color::Red);
+        assert_eq!(part.num_copies, 4);
+        assert_eq!(part.target, PartTarget::FourstarFocus);
+
+        assert!(banner_from_v3("not base64!").is_none());
+    }
+}
@@ -0,0 +1,103 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use strum_macros::EnumIter;
+
+use serde::{Deserialize, Serialize};
+
+pub mod banner;
+pub use banner::{Banner, BannerKind};
+
+pub mod goal;
+use goal::Goal;
+
+pub mod progress;
+pub use progress::Progress;
+
+pub mod pool_sizes;
+pub use pool_sizes::PoolSizes;
+
+pub mod templates;
+pub use templates::BannerTemplate;
+
+pub mod strategy;
+
+pub mod income;
+pub use income::IncomeProfile;
+
+pub mod legacy;
+
+pub mod sim;
+
+pub mod weighted_choice;
+
+pub mod stats;
+
+pub mod counter;
+use counter::Counter;
+
+/// The version of the permalink format, which has to change whenever the way
+/// that any of the settings in a permalink are stored changes. Links with an
+/// older version are either read with the decoders in `legacy` or rejected,
+/// rather than being misread.
+pub const PERMALINK_VERSION: &str = "5";
+
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, EnumIter, Serialize, Deserialize)]
+pub enum Color {
+    Red,
+    Blue,
+    Green,
+    Colorless,
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TryFrom<u8> for Color {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Color::*;
+        Ok(match value {
+            0 => Red,
+            1 => Blue,
+            2 => Green,
+            3 => Colorless,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Pool {
+    Focus,
+    Fivestar,
+    FourstarFocus,
+    Fourstar,
+    Threestar,
+    /// The special 4* rate, where any of the 5* focus units can appear as a
+    /// 4* unit.
+    FourstarSpecial,
+}
+
+impl TryFrom<u8> for Pool {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Pool::*;
+        Ok(match value {
+            0 => Focus,
+            1 => Fivestar,
+            2 => FourstarFocus,
+            3 => Fourstar,
+            4 => Threestar,
+            5 => FourstarSpecial,
+            _ => return Err(()),
+        })
+    }
+}
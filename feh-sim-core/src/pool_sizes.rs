@@ -0,0 +1,140 @@
+use lazy_static::lazy_static;
+
+/// The history of the pool sizes, one line per update. It is part of the
+/// build, so a malformed line is a bug rather than something to recover from.
+const POOL_SIZE_DATA: &str = include_str!("../data/pool_sizes.tsv");
+
+lazy_static! {
+    /// `POOL_SIZE_DATA` once it has been parsed, which only happens the first
+    /// time that it's needed.
+    static ref HISTORY: Vec<PoolSizes> = PoolSizes::parse_history(POOL_SIZE_DATA);
+}
+
+/// Number of units of each color in the non-focus summoning pools, as of some
+/// point in the game's history.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PoolSizes {
+    /// The month when these sizes took effect, as `YYYY-MM`. Permalinks use it
+    /// to refer to them.
+    pub date: &'static str,
+    pub fivestar: [u8; 4],
+    pub fourstar: [u8; 4],
+    pub threestar: [u8; 4],
+}
+
+impl Default for PoolSizes {
+    fn default() -> Self {
+        PoolSizes::latest()
+    }
+}
+
+impl PoolSizes {
+    /// Every version of the pool sizes, oldest first.
+    pub fn history() -> &'static [PoolSizes] {
+        &HISTORY
+    }
+
+    /// The pool sizes from before they could be chosen, which are the ones
+    /// that permalinks without a date were made with.
+    pub fn oldest() -> Self {
+        PoolSizes::history()[0]
+    }
+
+    /// The current pool sizes.
+    pub fn latest() -> Self {
+        *PoolSizes::history().last().unwrap()
+    }
+
+    /// Finds the pool sizes that were in effect in the given month, written
+    /// as `YYYY-MM`, which are the last ones to take effect in or before it.
+    pub fn from_date(date: &str) -> Option<Self> {
+        PoolSizes::find_date(PoolSizes::history(), date)
+    }
+
+    fn find_date(history: &[PoolSizes], date: &str) -> Option<Self> {
+        let bytes = date.as_bytes();
+        let is_month = bytes.len() == 7
+            && bytes[4] == b'-'
+            && bytes
+                .iter()
+                .enumerate()
+                .all(|(index, byte)| index == 4 || byte.is_ascii_digit());
+        if !is_month {
+            return None;
+        }
+        // Months written this way sort in the same order as strings.
+        history
+            .iter()
+            .rev()
+            .find(|sizes| sizes.date <= date)
+            .copied()
+    }
+
+    fn parse_history(data: &'static str) -> Vec<PoolSizes> {
+        data.lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(PoolSizes::parse_line)
+            .collect()
+    }
+
+    fn parse_line(line: &'static str) -> Self {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 13, "invalid pool size line \"{}\"", line);
+        let sizes = |start: usize| {
+            let mut sizes = [0; 4];
+            for (size, field) in sizes.iter_mut().zip(&fields[start..start + 4]) {
+                *size = field.parse().expect("invalid pool size");
+            }
+            sizes
+        };
+        PoolSizes {
+            date: fields[0],
+            fivestar: sizes(1),
+            fourstar: sizes(5),
+            threestar: sizes(9),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_is_parsed_in_order() {
+        let history = PoolSizes::history();
+        assert!(!history.is_empty());
+        assert!(history.windows(2).all(|pair| pair[0].date < pair[1].date));
+        assert_eq!(PoolSizes::oldest(), history[0]);
+        assert_eq!(PoolSizes::latest(), history[history.len() - 1]);
+        assert_eq!(PoolSizes::default(), PoolSizes::latest());
+    }
+
+    #[test]
+    fn from_date_finds_the_sizes_in_effect() {
+        let history = PoolSizes::parse_history(
+            "# date\t5* R\tB\tG\tC\t4* R\tB\tG\tC\t3* R\tB\tG\tC\n\
+             2019-12\t1\t2\t3\t4\t5\t6\t7\t8\t9\t10\t11\t12\n\
+             \n\
+             2023-02\t13\t14\t15\t16\t17\t18\t19\t20\t21\t22\t23\t24\n",
+        );
+        assert_eq!(history.len(), 2);
+        let older = PoolSizes::find_date(&history, "2019-12").unwrap();
+        assert_eq!(older.fivestar, [1, 2, 3, 4]);
+        assert_eq!(older.fourstar, [5, 6, 7, 8]);
+        assert_eq!(older.threestar, [9, 10, 11, 12]);
+        let newer = PoolSizes::find_date(&history, "2023-02").unwrap();
+        assert_eq!(newer.fivestar, [13, 14, 15, 16]);
+
+        // Months between the rows use the sizes that were in effect then.
+        assert_eq!(PoolSizes::find_date(&history, "2020-01"), Some(older));
+        assert_eq!(PoolSizes::find_date(&history, "2023-01"), Some(older));
+        assert_eq!(PoolSizes::find_date(&history, "2030-12"), Some(newer));
+        assert_eq!(PoolSizes::find_date(&history, "2019-11"), None);
+        assert_eq!(PoolSizes::find_date(&history, "2023-2"), None);
+
+        let latest = PoolSizes::latest();
+        assert_eq!(PoolSizes::from_date(latest.date), Some(latest));
+        assert_eq!(PoolSizes::from_date("not a date"), None);
+    }
+}
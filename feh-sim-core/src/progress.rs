@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// How far into a banner the summoner already is, so that a run can pick up
+/// from there instead of starting fresh.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Progress {
+    /// Number of summons since the 5* rates were last reset.
+    pub pity_count: u32,
+    /// Number of focus charges collected, from 0 to 3.
+    pub focus_charges: u32,
+    /// Number of summons made towards the next spark.
+    pub spark_count: u32,
+    /// Number of copies already owned for each part of the goal, in the same
+    /// order as the parts of the custom version of the goal. Missing entries
+    /// count as zero.
+    pub copies_owned: Vec<u8>,
+}
+
+impl Progress {
+    /// Gets the number of copies already owned for the part of the goal at
+    /// `index`.
+    pub fn copies_owned(&self, index: usize) -> u8 {
+        self.copies_owned.get(index).copied().unwrap_or(0)
+    }
+
+    /// Makes room for a new part of the goal at `index`, so that the owned
+    /// copies stay lined up with the parts after it.
+    pub fn insert_part(&mut self, index: usize) {
+        if index < self.copies_owned.len() {
+            self.copies_owned.insert(index, 0);
+        }
+    }
+
+    /// Parses data from the representation used in query strings to share settings.
+    pub fn from_query_string(s: &str) -> Option<Self> {
+        let data = base64::decode(s).ok()?;
+        bincode::deserialize(&data).ok()
+    }
+
+    /// Converts to the representation used in query strings to share settings.
+    pub fn to_query_string(&self) -> String {
+        base64::encode(&bincode::serialize(self).unwrap())
+    }
+}
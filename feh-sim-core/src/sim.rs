@@ -0,0 +1,1272 @@
+use crate::*;
+
+use std::sync::Arc;
+
+use rand::distributions::Distribution;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use weighted_choice::{WeightedIndex4, WeightedIndex6};
+
+use goal::{CustomGoal, GoalKind, GoalNode, GoalPart, PartTarget};
+
+use strategy::{Choice, PullStrategy, Strategy};
+
+pub mod campaign;
+pub mod exact;
+pub mod optimal;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
+
+/// The pity count at which the 5* rates reach 100%, so counting any higher
+/// makes no difference.
+const MAX_PITY_COUNT: u32 = 125;
+
+/// A run that spends this many orbs without reaching the goal gives up. Goals
+/// that pass `Goal::is_available` never come close, so this only guards
+/// against hanging forever on one that can't be reached.
+const MAX_ORBS: u32 = 100_000;
+
+/// Number of simulations in each independently seeded chunk of a run. Runs
+/// are split up this way so that the results for a given seed don't depend on
+/// how the work is divided up.
+pub const CHUNK_SIZE: u32 = 1000;
+
+/// The results of a pull session.
+struct SessionResult {
+    chosen_count: u32,
+    got_focus: bool,
+    nonfocus_count: u32,
+}
+
+struct PullOrbResult {
+    got_non_focus: bool,
+    got_focus: bool,
+}
+
+/// A structure holding the information for a sequence of summoning
+/// sessions done until a certain goal is reached. Keeps some cached information
+/// in order to make the simulation as fast as possible.
+#[derive(Debug, Clone)]
+pub struct Sim {
+    banner: Banner,
+    goal: CustomGoal,
+    /// The number of entries that each group in the goal needs and the group
+    /// that it's nested in, as laid out in `GoalData`.
+    goal_groups: Vec<(usize, usize)>,
+    /// Every part of the goal, in the order that progress lists them, along
+    /// with the group that it belongs to.
+    goal_parts: Vec<(GoalPart, usize)>,
+    pool_sizes: PoolSizes,
+    tables: RandTables,
+    rng: SmallRng,
+    goal_data: GoalData,
+    summon_count: u32,
+    progress: Progress,
+    strategy: Arc<dyn PullStrategy>,
+    budget: Option<u32>,
+    goal_copies: u32,
+    copies_at_budget: u32,
+}
+
+/// Precalculated tables for the probabilities of units being randomly chosen.
+#[derive(Debug, Copy, Clone, Default)]
+struct RandTables {
+    pool_sizes: [[u8; 4]; 6],
+    pool_dists: [WeightedIndex6; 26],
+    color_dists: [WeightedIndex4; 6],
+}
+
+/// Scratch space for representing the goal in a way that is faster to work with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct GoalData {
+    pub color_needed: [bool; 4],
+    pub copies_needed: [Vec<u8>; 4],
+    /// Whether each of the targets in `copies_needed` is a 4* focus unit.
+    /// These come first within each color, in the same order as the 4* focus
+    /// units they stand for.
+    pub is_fourstar: [Vec<bool>; 4],
+    /// Whether 4* copies of the 5* focus units count as well.
+    pub fourstar_merges: bool,
+    /// The number of entries that each group in the goal still needs, with
+    /// the whole goal as group 0.
+    pub groups_needed: Vec<usize>,
+    /// The group that each group is nested in. Group 0 is listed as its own
+    /// parent.
+    pub group_parents: Vec<usize>,
+    /// The group that each of the targets in `copies_needed` belongs to.
+    pub part_groups: [Vec<usize>; 4],
+    /// Copies still needed of each targeted unit in the non-focus 5* pool,
+    /// and the group that each one belongs to.
+    pub nonfocus_needed: [Vec<u8>; 4],
+    pub nonfocus_groups: [Vec<usize>; 4],
+    /// Copies still needed for each part that any 5* unit counts towards,
+    /// and the group that each one belongs to.
+    pub fivestar_needed: Vec<u8>,
+    pub fivestar_groups: Vec<usize>,
+}
+
+impl GoalData {
+    fn is_met(&self) -> bool {
+        self.groups_needed[0] == 0
+    }
+
+    /// Checks whether the group or any group that it's nested in has been
+    /// achieved, so that its targets no longer matter.
+    fn is_settled(&self, mut group: usize) -> bool {
+        loop {
+            if self.groups_needed[group] == 0 {
+                return true;
+            }
+            if group == 0 {
+                return false;
+            }
+            group = self.group_parents[group];
+        }
+    }
+
+    /// Records that one of the entries in the given group was achieved, then
+    /// drops the targets that no longer matter because of it.
+    fn finish_entry(&mut self, mut group: usize) {
+        loop {
+            self.groups_needed[group] = self.groups_needed[group].saturating_sub(1);
+            if self.groups_needed[group] > 0 || group == 0 {
+                break;
+            }
+            group = self.group_parents[group];
+        }
+        for color in 0..4 {
+            let mut position = 0;
+            while position < self.part_groups[color].len() {
+                if self.is_settled(self.part_groups[color][position]) {
+                    self.copies_needed[color].remove(position);
+                    self.is_fourstar[color].remove(position);
+                    self.part_groups[color].remove(position);
+                } else {
+                    position += 1;
+                }
+            }
+            let mut position = 0;
+            while position < self.nonfocus_groups[color].len() {
+                if self.is_settled(self.nonfocus_groups[color][position]) {
+                    self.nonfocus_needed[color].remove(position);
+                    self.nonfocus_groups[color].remove(position);
+                } else {
+                    position += 1;
+                }
+            }
+        }
+        let mut position = 0;
+        while position < self.fivestar_groups.len() {
+            if self.is_settled(self.fivestar_groups[position]) {
+                self.fivestar_needed.remove(position);
+                self.fivestar_groups.remove(position);
+            } else {
+                position += 1;
+            }
+        }
+        self.update_color_needed();
+    }
+
+    /// Works out which colors still have targets left in them.
+    fn update_color_needed(&mut self) {
+        for color in 0..4 {
+            self.color_needed[color] = !self.copies_needed[color].is_empty()
+                || !self.nonfocus_needed[color].is_empty()
+                || !self.fivestar_needed.is_empty();
+        }
+    }
+
+    /// Checks whether the given sample could be one of the targeted units.
+    fn may_progress(&self, sample: (Pool, Color)) -> bool {
+        let color = sample.1 as usize;
+        match sample.0 {
+            Pool::Focus => {
+                !self.copies_needed[color].is_empty() || !self.fivestar_needed.is_empty()
+            }
+            Pool::Fivestar => {
+                !self.nonfocus_needed[color].is_empty() || !self.fivestar_needed.is_empty()
+            }
+            Pool::FourstarFocus => self.is_fourstar[color].contains(&true),
+            Pool::FourstarSpecial => {
+                !self.copies_needed[color].is_empty()
+                    && (self.fourstar_merges || self.is_fourstar[color].contains(&true))
+            }
+            Pool::Fourstar | Pool::Threestar => false,
+        }
+    }
+
+    /// Records the `which_unit`th unit of the sample's pool and color, doing
+    /// nothing if that unit isn't one of the targets. Parts that count any 5*
+    /// unit are left to `add_fivestar`. Returns whether the unit counted
+    /// towards the goal.
+    fn add_unit(&mut self, sample: (Pool, Color), which_unit: usize) -> bool {
+        let color = sample.1;
+        match sample.0 {
+            Pool::Focus => self.add_copy(color, which_unit),
+            Pool::Fivestar => self.add_nonfocus_copy(color, which_unit),
+            Pool::FourstarFocus => self.add_fourstar_copy(color, which_unit),
+            Pool::FourstarSpecial => self.add_special_copy(color, which_unit),
+            Pool::Fourstar | Pool::Threestar => false,
+        }
+    }
+
+    /// Records a copy of the `which_unit`th targeted unit of the given color,
+    /// doing nothing if that unit isn't one of the targets. Returns whether the
+    /// copy counted towards the goal.
+    fn add_copy(&mut self, color: Color, which_unit: usize) -> bool {
+        let copies_needed = &mut self.copies_needed[color as usize];
+        if which_unit >= copies_needed.len() {
+            return false;
+        }
+        if copies_needed[which_unit] > 1 {
+            copies_needed[which_unit] -= 1;
+        } else {
+            copies_needed.remove(which_unit);
+            self.is_fourstar[color as usize].remove(which_unit);
+            let group = self.part_groups[color as usize].remove(which_unit);
+            self.finish_entry(group);
+        }
+        true
+    }
+
+    /// Records a copy of the `which_unit`th targeted unit of the given color
+    /// in the non-focus 5* pool, doing nothing if that unit isn't one of the
+    /// targets. Returns whether the copy counted towards the goal.
+    fn add_nonfocus_copy(&mut self, color: Color, which_unit: usize) -> bool {
+        let nonfocus_needed = &mut self.nonfocus_needed[color as usize];
+        if which_unit >= nonfocus_needed.len() {
+            return false;
+        }
+        if nonfocus_needed[which_unit] > 1 {
+            nonfocus_needed[which_unit] -= 1;
+        } else {
+            nonfocus_needed.remove(which_unit);
+            let group = self.nonfocus_groups[color as usize].remove(which_unit);
+            self.finish_entry(group);
+        }
+        true
+    }
+
+    /// Records a 5* unit for every part that counts any 5* unit. Returns
+    /// whether there were any such parts left.
+    fn add_fivestar(&mut self) -> bool {
+        if self.fivestar_needed.is_empty() {
+            return false;
+        }
+        for copies in &mut self.fivestar_needed {
+            *copies = copies.saturating_sub(1);
+        }
+        // Finishing one part can drop others, so the finished parts are
+        // looked up again each time.
+        while let Some(position) = self.fivestar_needed.iter().position(|&copies| copies == 0) {
+            self.fivestar_needed.remove(position);
+            let group = self.fivestar_groups.remove(position);
+            self.finish_entry(group);
+        }
+        true
+    }
+
+    /// Records a 4* copy of the `which_unit`th 4* focus unit of the given
+    /// color, doing nothing if that unit isn't one of the targets. Returns
+    /// whether the copy counted towards the goal.
+    fn add_fourstar_copy(&mut self, color: Color, which_unit: usize) -> bool {
+        if self.is_fourstar[color as usize].get(which_unit) == Some(&true) {
+            self.add_copy(color, which_unit)
+        } else {
+            false
+        }
+    }
+
+    /// Records a 4* copy of the `which_unit`th 5* focus unit of the given
+    /// color from the special 4* rate, which only counts if it is also one of
+    /// the 4* focus units or the goal counts 4* copies as merges. Returns
+    /// whether the copy counted towards the goal.
+    fn add_special_copy(&mut self, color: Color, which_unit: usize) -> bool {
+        if self.fourstar_merges || self.is_fourstar[color as usize].get(which_unit) == Some(&true) {
+            self.add_copy(color, which_unit)
+        } else {
+            false
+        }
+    }
+
+    /// Takes a free copy of whichever targeted focus unit is closest to being
+    /// finished, which is the choice that cuts the remaining work the most.
+    /// The copy is a 5* unit either way, so it also counts for the parts that
+    /// take any 5* unit. Returns whether the copy counted towards the goal,
+    /// which it never does once the goal has been met.
+    fn spark(&mut self, focus_sizes: [i8; 4]) -> bool {
+        if self.is_met() {
+            return false;
+        }
+        let closest = (0..4)
+            .flat_map(|color| {
+                let focus_count = focus_sizes[color].max(0) as usize;
+                self.copies_needed[color]
+                    .iter()
+                    .take(focus_count)
+                    .enumerate()
+                    .map(move |(which_unit, &copies)| (copies, color, which_unit))
+            })
+            .min();
+        let added = if let Some((_, color, which_unit)) = closest {
+            self.add_copy(Color::try_from(color as u8).unwrap(), which_unit)
+        } else {
+            false
+        };
+        let added_fivestar = self.add_fivestar();
+        added || added_fivestar
+    }
+}
+
+impl Sim {
+    /// Creates a new simulator for the given banner and goal, doing some
+    /// moderately expensive initialization. Avoid running in a hot loop, but
+    /// it's not a problem to call somewhat frequently. Simulators created with
+    /// the same seed produce the same results.
+    pub fn new(banner: Banner, goal: Goal, seed: u64) -> Self {
+        let mut sim = Sim {
+            goal: goal.as_custom(&banner),
+            banner,
+            goal_groups: vec![],
+            goal_parts: vec![],
+            pool_sizes: PoolSizes::default(),
+            tables: RandTables::default(),
+            rng: SmallRng::seed_from_u64(seed),
+            goal_data: GoalData::default(),
+            summon_count: 0,
+            progress: Progress::default(),
+            strategy: Arc::new(Strategy::default()),
+            budget: None,
+            goal_copies: 0,
+            copies_at_budget: 0,
+        };
+        sim.init_probability_tables();
+        sim.init_goal_layout();
+        sim
+    }
+
+    /// Initializes the precalculated tables used for fast random sampling.
+    fn init_probability_tables(&mut self) {
+        let pool_sizes = self.banner.pool_sizes(self.pool_sizes);
+        self.tables.pool_sizes = [
+            [0, 0, 0, 0],
+            pool_sizes.fivestar,
+            [0, 0, 0, 0],
+            pool_sizes.fourstar,
+            pool_sizes.threestar,
+            [0, 0, 0, 0],
+        ];
+        for i in 0..4 {
+            self.tables.pool_sizes[0][i] = self.banner.focus_sizes[i].max(0) as u8;
+        }
+        self.tables.pool_sizes[2] = self.banner.fourstar_focus_sizes;
+        self.tables.pool_sizes[5] = self.tables.pool_sizes[0];
+
+        for color in 0..6 {
+            self.tables.color_dists[color] = WeightedIndex4::new(self.tables.pool_sizes[color]);
+        }
+
+        for pity_incr in 0..26 {
+            self.tables.pool_dists[pity_incr] =
+                WeightedIndex6::new(self.probabilities(pity_incr as u32));
+        }
+    }
+
+    /// Numbers the groups in the goal in the same order as
+    /// `CustomGoal::group_mut`, and lists the goal's parts along with the group
+    /// that each one belongs to.
+    fn init_goal_layout(&mut self) {
+        fn add_group(
+            kind: GoalKind,
+            nodes: &[GoalNode],
+            parent: usize,
+            groups: &mut Vec<(usize, usize)>,
+            parts: &mut Vec<(GoalPart, usize)>,
+        ) {
+            let group = groups.len();
+            groups.push((kind.parts_needed(nodes.len()), parent));
+            for node in nodes {
+                match node {
+                    GoalNode::Part(part) => parts.push((*part, group)),
+                    GoalNode::Group(nested) => {
+                        add_group(nested.kind, &nested.goals, group, groups, parts)
+                    }
+                }
+            }
+        }
+        add_group(
+            self.goal.kind,
+            &self.goal.goals,
+            0,
+            &mut self.goal_groups,
+            &mut self.goal_parts,
+        );
+    }
+
+    // Initializes the internal representation of a goal.
+    fn init_goal_data(&mut self) {
+        self.goal_data.fourstar_merges = self.goal.fourstar_merges;
+        self.goal_data.groups_needed.clear();
+        self.goal_data.group_parents.clear();
+        for &(needed, parent) in &self.goal_groups {
+            self.goal_data.groups_needed.push(needed);
+            self.goal_data.group_parents.push(parent);
+        }
+        for i in 0..4 {
+            self.goal_data.copies_needed[i].clear();
+            self.goal_data.is_fourstar[i].clear();
+            self.goal_data.part_groups[i].clear();
+            self.goal_data.nonfocus_needed[i].clear();
+            self.goal_data.nonfocus_groups[i].clear();
+        }
+        self.goal_data.fivestar_needed.clear();
+        self.goal_data.fivestar_groups.clear();
+        let progress = &self.progress;
+        let is_done = |index: usize, goal: GoalPart| {
+            let copies_owned = progress.copies_owned(index);
+            copies_owned > 0 && copies_owned >= goal.num_copies
+        };
+        for (index, &(goal, group)) in self.goal_parts.iter().enumerate() {
+            if is_done(index, goal) {
+                // This part is already done, so it doesn't need to be tracked.
+                // It's counted once everything else is in place, since it
+                // might mean that some of the others don't need to be done.
+                continue;
+            }
+            let copies_needed = goal.num_copies - progress.copies_owned(index);
+            let color = goal.unit_color as usize;
+            match goal.target {
+                PartTarget::Focus | PartTarget::FourstarFocus => {
+                    let four_star = goal.target == PartTarget::FourstarFocus;
+                    let is_fourstar = &mut self.goal_data.is_fourstar[color];
+                    let position = if four_star {
+                        is_fourstar
+                            .iter()
+                            .filter(|&&is_fourstar| is_fourstar)
+                            .count()
+                    } else {
+                        is_fourstar.len()
+                    };
+                    is_fourstar.insert(position, four_star);
+                    self.goal_data.copies_needed[color].insert(position, copies_needed);
+                    self.goal_data.part_groups[color].insert(position, group);
+                }
+                PartTarget::NonFocus => {
+                    self.goal_data.nonfocus_needed[color].push(copies_needed);
+                    self.goal_data.nonfocus_groups[color].push(group);
+                }
+                PartTarget::AnyFivestar => {
+                    self.goal_data.fivestar_needed.push(copies_needed);
+                    self.goal_data.fivestar_groups.push(group);
+                }
+            }
+        }
+        self.goal_data.update_color_needed();
+        for (index, &(goal, group)) in self.goal_parts.iter().enumerate() {
+            if is_done(index, goal) && !self.goal_data.is_settled(group) {
+                self.goal_data.finish_entry(group);
+            }
+        }
+    }
+
+    /// Sets how far into the banner the summoner already is, which every
+    /// simulation starts from.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    /// Sets the sizes of the non-focus pools, for simulating the game as it was
+    /// at some other point in time.
+    pub fn set_pool_sizes(&mut self, pool_sizes: PoolSizes) {
+        self.pool_sizes = pool_sizes;
+        self.init_probability_tables();
+    }
+
+    /// Sets the strategy used to decide which stones to pull in each session.
+    pub fn set_strategy(&mut self, strategy: impl PullStrategy + 'static) {
+        self.strategy = Arc::new(strategy);
+    }
+
+    /// The pity count and number of focus charges that each simulation starts
+    /// with.
+    fn starting_pity(&self) -> (u32, u32) {
+        let focus_charges = if self.banner.focus_charges {
+            self.progress.focus_charges.min(3)
+        } else {
+            0
+        };
+        (self.progress.pity_count.min(MAX_PITY_COUNT), focus_charges)
+    }
+
+    /// Replaces the random number generator with one seeded from `seed`, so
+    /// that the following simulations can be reproduced.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    /// Sets the number of orbs available to spend. Runs that don't reach the
+    /// goal within the budget keep going, but also record how many copies of
+    /// the targeted units they had gotten when the budget ran out.
+    pub fn set_budget(&mut self, budget: Option<u32>) {
+        self.budget = budget;
+    }
+
+    /// Runs the simulations for chunk number `chunk` of the run with the given
+    /// seed, adding the results to `data`. Only the first `samples` of the
+    /// chunk's simulations are run, which should be at most `CHUNK_SIZE`. If
+    /// there is a budget, each run that goes over it adds the number of goal
+    /// copies it had at that point to `budget_copies`.
+    pub fn run_chunk(
+        &mut self,
+        seed: u64,
+        chunk: u64,
+        samples: u32,
+        data: &mut Counter,
+        budget_copies: &mut Counter,
+    ) {
+        debug_assert!(samples <= CHUNK_SIZE);
+        self.reseed(chunk_seed(seed, chunk));
+        for _ in 0..samples {
+            let orbs = self.roll_until_goal();
+            data[orbs] += 1;
+            if let Some(budget) = self.budget {
+                if orbs > budget {
+                    budget_copies[self.copies_at_budget] += 1;
+                }
+            }
+        }
+    }
+
+    /// Simulates until reaching the current goal, then returns # of orbs used.
+    pub fn roll_until_goal(&mut self) -> u32 {
+        self.roll(None).0
+    }
+
+    /// Simulates until reaching the current goal or running out of orbs, then
+    /// returns the number of orbs used and whether the goal was reached. The
+    /// last session stops early if the next stone would cost more than is
+    /// left.
+    pub fn roll_within_budget(&mut self, orbs: u32) -> (u32, bool) {
+        self.roll(Some(orbs))
+    }
+
+    /// Simulates until reaching the current goal or, if there is a limit,
+    /// until not even one more stone can be afforded. Gives up after
+    /// `MAX_ORBS` orbs either way.
+    fn roll(&mut self, orb_limit: Option<u32>) -> (u32, bool) {
+        let (mut pity_count, mut focus_charges) = self.starting_pity();
+        let mut orb_count = 0;
+        self.summon_count = self.progress.spark_count;
+        self.goal_copies = 0;
+        self.copies_at_budget = 0;
+        self.init_goal_data();
+        if self.goal_data.is_met() {
+            return (0, true);
+        }
+        loop {
+            if orb_count >= MAX_ORBS {
+                return (orb_count, false);
+            }
+            let max_pulls = match orb_limit {
+                Some(limit) => match (1..=5)
+                    .rev()
+                    .find(|&count| orb_count + Sim::orb_cost(count) <= limit)
+                {
+                    Some(count) => count,
+                    None => return (orb_count, false),
+                },
+                None => 5,
+            };
+            // The count keeps going past the last increase on banners where
+            // the 5* rates don't reach 100%, such as ones with a 4* focus unit.
+            let pity_incr = (pity_count / 5).min(25);
+            let samples = [
+                self.sample(pity_incr, focus_charges == 3),
+                self.sample(pity_incr, focus_charges == 3),
+                self.sample(pity_incr, focus_charges == 3),
+                self.sample(pity_incr, focus_charges == 3),
+                self.sample(pity_incr, focus_charges == 3),
+            ];
+            let SessionResult {
+                chosen_count,
+                got_focus,
+                nonfocus_count,
+            } = self.session_select(&samples, pity_incr, orb_count, max_pulls);
+            self.update_pity(
+                &mut pity_count,
+                &mut focus_charges,
+                chosen_count,
+                got_focus,
+                nonfocus_count,
+            );
+            orb_count += Sim::orb_cost(chosen_count);
+            if self.goal_data.is_met() {
+                return (orb_count, true);
+            }
+        }
+    }
+
+    /// Updates the pity rate and focus charges after a session with the given
+    /// results.
+    fn update_pity(
+        &self,
+        pity_count: &mut u32,
+        focus_charges: &mut u32,
+        chosen_count: u32,
+        got_focus: bool,
+        nonfocus_count: u32,
+    ) {
+        *pity_count += chosen_count;
+        if got_focus {
+            *pity_count = 0;
+        } else {
+            *pity_count = pity_count.saturating_sub(20 * nonfocus_count);
+        }
+        if got_focus && *focus_charges == 3 {
+            *focus_charges = 0;
+        }
+        if self.banner.focus_charges {
+            *focus_charges = (*focus_charges + nonfocus_count).min(3);
+            if got_focus {
+                *focus_charges = 0;
+            }
+        }
+    }
+
+    /// Given a session with five randomly-selected units, decides which ones
+    /// would be chosen to achieve the current goal, then evaluates the results
+    /// of choosing them. `orb_count` is the number of orbs spent before the
+    /// session, and the session ends once `max_pulls` stones have been chosen.
+    fn session_select(
+        &mut self,
+        samples: &[(Pool, Color); 5],
+        pity_incr: u32,
+        orb_count: u32,
+        max_pulls: u32,
+    ) -> SessionResult {
+        let mut result = SessionResult {
+            chosen_count: 0,
+            got_focus: false,
+            nonfocus_count: 0,
+        };
+        let mut off_color_count = 0;
+        let mut held = None;
+        for (i, &sample) in samples.iter().enumerate() {
+            let choice = Choice {
+                color: sample.1,
+                index: i,
+                chosen_count: result.chosen_count,
+                off_color_count,
+                color_needed: self.goal_data.color_needed,
+                pity_incr,
+            };
+            if let Some(sample) = self.chosen_stone(&choice, sample, held) {
+                if !self.goal_data.color_needed[sample.1 as usize] {
+                    off_color_count += 1;
+                }
+                result.chosen_count += 1;
+                let pull_result = self.pull_orb(sample);
+                result.got_focus |= pull_result.got_focus;
+                result.nonfocus_count += if pull_result.got_non_focus { 1 } else { 0 };
+                self.summon_count += 1;
+                if self.spark_available() && self.goal_data.spark(self.banner.focus_sizes) {
+                    self.goal_copies += 1;
+                }
+                if let Some(budget) = self.budget {
+                    if orb_count + Sim::orb_cost(result.chosen_count) <= budget {
+                        self.copies_at_budget = self.goal_copies;
+                    }
+                }
+                if self.goal_data.is_met() || result.chosen_count == max_pulls {
+                    return result;
+                }
+            } else {
+                held = self.held_stone(&choice, sample, held);
+            }
+        }
+        result
+    }
+
+    /// Checks whether the summon that was just made earned a spark.
+    fn spark_available(&self) -> bool {
+        match self.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => self.summon_count % threshold == 0,
+            _ => false,
+        }
+    }
+
+    /// Decides which stone, if any, would be pulled at this point in the
+    /// session. That's up to the strategy, except that at least one stone has
+    /// to be chosen in every session, so the last stone of a session with none
+    /// chosen pulls the held stone if there is one, or else itself.
+    fn chosen_stone(
+        &self,
+        choice: &Choice,
+        sample: (Pool, Color),
+        held: Option<Pool>,
+    ) -> Option<(Pool, Color)> {
+        if self.strategy.is_chosen(choice) {
+            Some(sample)
+        } else if choice.index == 4 && choice.chosen_count == 0 {
+            match (held, self.strategy.fallback_color()) {
+                (Some(pool), Some(color)) => Some((pool, color)),
+                _ => Some(sample),
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Finds the pool of the stone held back for the strategy's fallback color
+    /// after passing on the given stone. Only the first stone of that color is
+    /// kept, and only until something is chosen.
+    fn held_stone(
+        &self,
+        choice: &Choice,
+        sample: (Pool, Color),
+        held: Option<Pool>,
+    ) -> Option<Pool> {
+        if choice.chosen_count > 0 {
+            None
+        } else if held.is_none() && self.strategy.fallback_color() == Some(sample.1) {
+            Some(sample.0)
+        } else {
+            held
+        }
+    }
+
+    /// Evaluates the result of selecting the given sample.
+    fn pull_orb(&mut self, sample: (Pool, Color)) -> PullOrbResult {
+        let result = PullOrbResult {
+            got_focus: sample.0 == Pool::Focus,
+            got_non_focus: sample.0 == Pool::Fivestar,
+        };
+        if !self.goal_data.may_progress(sample) {
+            return result;
+        }
+        let which_unit = self.rng.gen::<usize>() % self.unit_count(sample);
+        let added = self.goal_data.add_unit(sample, which_unit);
+        let added_fivestar = is_fivestar(sample.0) && self.goal_data.add_fivestar();
+        if added || added_fivestar {
+            self.goal_copies += 1;
+        }
+        result
+    }
+
+    /// The number of different units that the given sample could be.
+    fn unit_count(&self, sample: (Pool, Color)) -> usize {
+        self.tables.pool_sizes[sample.0 as usize][sample.1 as usize] as usize
+    }
+
+    /// The total orb cost of choosing the given number of units from a session.
+    fn orb_cost(count: u32) -> u32 {
+        match count {
+            1 => 5,
+            2 => 9,
+            3 => 13,
+            4 => 17,
+            5 => 20,
+            _ => panic!("Invalid orb cost: {}", count),
+        }
+    }
+
+    /// Chooses a weighted random unit from the summoning pool. `pity_incr` is the
+    /// number of times that the 5* rates have increased by 0.5% total.
+    fn sample(&mut self, pity_incr: u32, focus_charge_active: bool) -> (Pool, Color) {
+        let pool = self.tables.pool_dists[pity_incr as usize].sample(&mut self.rng) as u8;
+        let mut pool = Pool::try_from(pool).unwrap();
+        if focus_charge_active && pool == Pool::Fivestar {
+            pool = Pool::Focus;
+        }
+
+        let color = self.tables.color_dists[pool as usize].sample(&mut self.rng) as u8;
+        let color = Color::try_from(color).unwrap();
+        (pool, color)
+    }
+
+    /// Calculates the actual probabilities of selecting a unit from each of the
+    /// possible pools after a certain number of rate increases. The increases
+    /// only come out of the non-focus 4* and 3* rates, so the 4* focus and
+    /// special 4* rates stay the same.
+    fn probabilities(&self, pity_incr: u32) -> [f32; 6] {
+        let bases = self.banner.starting_rates;
+        // Rates with only a little left for the non-focus 4* and 3* units run
+        // out of it before the last increase.
+        let lower_total = bases[Pool::Fourstar as usize] + bases[Pool::Threestar as usize];
+        let pity_pct = if pity_incr >= 25 {
+            lower_total
+        } else {
+            (pity_incr as f32 * 0.5).min(lower_total)
+        };
+
+        let mut probabilities = bases;
+        let focus_ratio = bases[Pool::Focus as usize]
+            / (bases[Pool::Focus as usize] + bases[Pool::Fivestar as usize]);
+        probabilities[Pool::Focus as usize] += pity_pct * focus_ratio;
+        probabilities[Pool::Fivestar as usize] += pity_pct * (1.0 - focus_ratio);
+
+        let lower_ratio = bases[Pool::Fourstar as usize]
+            / (bases[Pool::Fourstar as usize] + bases[Pool::Threestar as usize]);
+        probabilities[Pool::Fourstar as usize] -= pity_pct * lower_ratio;
+        probabilities[Pool::Threestar as usize] -= pity_pct * (1.0 - lower_ratio);
+        probabilities
+    }
+
+    /// The possible goal states after pulling the given sample, mirroring
+    /// `Sim::pull_orb`.
+    fn pull_outcomes(&self, goal_data: &GoalData, sample: (Pool, Color)) -> Vec<(GoalData, f64)> {
+        if !goal_data.may_progress(sample) {
+            return vec![(goal_data.clone(), 1.0)];
+        }
+        let unit_count = self.unit_count(sample);
+        // Only the units listed in the goal can count by themselves, and every
+        // other unit leaves the goal in the same state.
+        let listed = if sample.0 == Pool::Fivestar {
+            goal_data.nonfocus_needed[sample.1 as usize].len()
+        } else {
+            goal_data.copies_needed[sample.1 as usize].len()
+        };
+        let mut outcomes = vec![];
+        let mut missed = unit_count - listed.min(unit_count);
+        for which_unit in 0..listed.min(unit_count) {
+            let mut next = goal_data.clone();
+            if next.add_unit(sample, which_unit) {
+                if is_fivestar(sample.0) {
+                    next.add_fivestar();
+                }
+                outcomes.push((next, 1.0 / unit_count as f64));
+            } else {
+                missed += 1;
+            }
+        }
+        if missed > 0 {
+            let mut next = goal_data.clone();
+            if is_fivestar(sample.0) {
+                next.add_fivestar();
+            }
+            outcomes.push((next, missed as f64 / unit_count as f64));
+        }
+        outcomes
+    }
+
+    /// The probability of each possible sample, matching the distributions
+    /// used by `Sim::sample`.
+    fn sample_probabilities(
+        &self,
+        pity_incr: u32,
+        focus_charge_active: bool,
+    ) -> Vec<((Pool, Color), f64)> {
+        let weights = self.probabilities(pity_incr);
+        let total: f32 = weights.iter().sum();
+        let mut pool_probabilities = [0.0; 6];
+        for (probability, &weight) in pool_probabilities.iter_mut().zip(&weights) {
+            *probability = (weight / total) as f64;
+        }
+        if focus_charge_active {
+            pool_probabilities[Pool::Focus as usize] += pool_probabilities[Pool::Fivestar as usize];
+            pool_probabilities[Pool::Fivestar as usize] = 0.0;
+        }
+
+        let mut samples = vec![];
+        for (pool, &pool_probability) in pool_probabilities.iter().enumerate() {
+            let sizes = self.tables.pool_sizes[pool];
+            let total: u32 = sizes.iter().map(|&size| size as u32).sum();
+            if pool_probability <= 0.0 || total == 0 {
+                continue;
+            }
+            for (color, &size) in sizes.iter().enumerate() {
+                if size > 0 {
+                    samples.push((
+                        (
+                            Pool::try_from(pool as u8).unwrap(),
+                            Color::try_from(color as u8).unwrap(),
+                        ),
+                        pool_probability * size as f64 / total as f64,
+                    ));
+                }
+            }
+        }
+        samples
+    }
+}
+
+/// Whether the `count`th stone pulled in a session earns a spark, given the
+/// number of summons until the session's first spark. Sparks then come every
+/// `threshold` summons, so thresholds below 5 can spark more than once in a
+/// session.
+fn is_spark_summon(count: u32, summons_until_spark: u32, threshold: u32) -> bool {
+    count >= summons_until_spark && (count - summons_until_spark) % threshold == 0
+}
+
+/// Whether units from the given pool are 5* units.
+fn is_fivestar(pool: Pool) -> bool {
+    pool == Pool::Focus || pool == Pool::Fivestar
+}
+
+/// Derives the seed for a single chunk of a run. Each step is mixed so that
+/// runs with nearby seeds don't share any chunks.
+fn chunk_seed(seed: u64, chunk: u64) -> u64 {
+    splitmix64(seed.wrapping_add(splitmix64(chunk)))
+}
+
+/// The output function of the SplitMix64 generator, which scrambles the bits
+/// of its input.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::goal::{GoalGroup, GoalPreset};
+
+    fn part(unit_color: Color, num_copies: u8, target: PartTarget) -> GoalNode {
+        GoalNode::Part(GoalPart {
+            unit_color,
+            num_copies,
+            target,
+            unit: None,
+        })
+    }
+
+    /// A simulator for a custom goal on the given banner, with its goal data
+    /// set up as it is at the start of a run.
+    fn custom_sim(banner: Banner, kind: GoalKind, goals: Vec<GoalNode>) -> Sim {
+        let goal = Goal::Custom(CustomGoal {
+            kind,
+            goals,
+            fourstar_merges: false,
+        });
+        let mut sim = Sim::new(banner, goal, 0);
+        sim.init_goal_data();
+        sim
+    }
+
+    #[test]
+    fn spark_caps_the_orbs_spent() {
+        // Even at one stone per session, 40 summons cost at most 200 orbs,
+        // and the spark after them gets the unit.
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::RedFocus, 1), 0);
+        for _ in 0..1000 {
+            assert!(sim.roll_until_goal() <= 200);
+        }
+    }
+
+    #[test]
+    fn spark_takes_the_unit_closest_to_done() {
+        let mut sim = custom_sim(
+            Banner::default(),
+            GoalKind::All,
+            vec![
+                part(Color::Red, 3, PartTarget::Focus),
+                part(Color::Blue, 1, PartTarget::Focus),
+            ],
+        );
+        assert!(sim.goal_data.spark(sim.banner.focus_sizes));
+        assert_eq!(sim.goal_data.copies_needed[Color::Red as usize], vec![3]);
+        assert!(sim.goal_data.copies_needed[Color::Blue as usize].is_empty());
+        assert!(!sim.goal_data.color_needed[Color::Blue as usize]);
+        assert!(!sim.goal_data.is_met());
+    }
+
+    #[test]
+    fn owned_copies_count_towards_the_goal() {
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::RedFocus, 3), 0);
+        sim.set_progress(Progress {
+            copies_owned: vec![3],
+            ..Progress::default()
+        });
+        assert_eq!(sim.roll_within_budget(0), (0, true));
+
+        sim.set_progress(Progress {
+            copies_owned: vec![2],
+            ..Progress::default()
+        });
+        sim.init_goal_data();
+        assert_eq!(sim.goal_data.copies_needed[Color::Red as usize], vec![1]);
+    }
+
+    #[test]
+    fn spark_progress_carries_over() {
+        // The first stone pulled is the 40th summon, so its spark finishes the
+        // goal whatever it turns out to be.
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::RedFocus, 1), 0);
+        sim.set_progress(Progress {
+            spark_count: 39,
+            ..Progress::default()
+        });
+        for _ in 0..100 {
+            assert_eq!(sim.roll_until_goal(), 5);
+        }
+    }
+
+    #[test]
+    fn starting_pity_is_capped() {
+        let mut sim = Sim::new(Banner::default(), Goal::default(), 0);
+        sim.set_progress(Progress {
+            pity_count: 1000,
+            focus_charges: 5,
+            ..Progress::default()
+        });
+        assert_eq!(sim.starting_pity(), (MAX_PITY_COUNT, 3));
+    }
+
+    #[test]
+    fn fourstar_copies_go_to_their_own_units() {
+        let banner = Banner {
+            fourstar_focus_sizes: [2, 0, 0, 0],
+            ..Banner::default()
+        };
+        let mut sim = custom_sim(
+            banner,
+            GoalKind::All,
+            vec![
+                part(Color::Red, 1, PartTarget::Focus),
+                part(Color::Red, 1, PartTarget::FourstarFocus),
+                part(Color::Red, 2, PartTarget::FourstarFocus),
+            ],
+        );
+        assert_eq!(sim.unit_count((Pool::FourstarFocus, Color::Red)), 2);
+        let goal_data = &mut sim.goal_data;
+        assert_eq!(
+            goal_data.is_fourstar[Color::Red as usize],
+            vec![true, true, false]
+        );
+        assert_eq!(goal_data.copies_needed[Color::Red as usize], vec![1, 2, 1]);
+
+        assert!(goal_data.add_unit((Pool::FourstarFocus, Color::Red), 1));
+        assert_eq!(goal_data.copies_needed[Color::Red as usize], vec![1, 1, 1]);
+        assert!(goal_data.add_unit((Pool::FourstarFocus, Color::Red), 0));
+        assert_eq!(goal_data.copies_needed[Color::Red as usize], vec![1, 1]);
+        assert_eq!(
+            goal_data.is_fourstar[Color::Red as usize],
+            vec![true, false]
+        );
+        // The 5* focus unit only shows up at 5*.
+        assert!(!goal_data.add_unit((Pool::FourstarFocus, Color::Red), 1));
+    }
+
+    #[test]
+    fn special_fourstar_copies_count_as_merges_when_asked() {
+        let banner = Banner {
+            starting_rates: [3.0, 3.0, 0.0, 55.0, 36.0, 3.0],
+            ..Banner::default()
+        };
+        let mut sim = custom_sim(
+            banner,
+            GoalKind::All,
+            vec![part(Color::Red, 2, PartTarget::Focus)],
+        );
+        let special: f64 = sim
+            .sample_probabilities(0, false)
+            .iter()
+            .filter(|&&((pool, _), _)| pool == Pool::FourstarSpecial)
+            .map(|&(_, probability)| probability)
+            .sum();
+        assert!((special - 0.03).abs() < 1e-6);
+
+        let sample = (Pool::FourstarSpecial, Color::Red);
+        assert!(!sim.goal_data.may_progress(sample));
+        assert!(!sim.goal_data.add_unit(sample, 0));
+
+        sim.goal.fourstar_merges = true;
+        sim.init_goal_data();
+        assert!(sim.goal_data.may_progress(sample));
+        assert!(sim.goal_data.add_unit(sample, 0));
+        assert_eq!(sim.goal_data.copies_needed[Color::Red as usize], vec![1]);
+    }
+
+    #[test]
+    fn at_least_goals_stop_once_enough_parts_are_done() {
+        let mut sim = custom_sim(
+            Banner::default(),
+            GoalKind::AtLeast(2),
+            vec![
+                part(Color::Red, 1, PartTarget::Focus),
+                part(Color::Blue, 1, PartTarget::Focus),
+                part(Color::Green, 1, PartTarget::Focus),
+            ],
+        );
+        assert_eq!(sim.goal_data.groups_needed, vec![2]);
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Red), 0));
+        assert!(!sim.goal_data.is_met());
+        assert_eq!(sim.goal_data.color_needed, [false, true, true, false]);
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Green), 0));
+        assert!(sim.goal_data.is_met());
+        assert_eq!(sim.goal_data.color_needed, [false; 4]);
+    }
+
+    #[test]
+    fn nested_groups_drop_the_parts_they_no_longer_need() {
+        // Red, and either blue or green.
+        let either = GoalNode::Group(GoalGroup {
+            kind: GoalKind::Any,
+            goals: vec![
+                part(Color::Blue, 1, PartTarget::Focus),
+                part(Color::Green, 1, PartTarget::Focus),
+            ],
+        });
+        let mut sim = custom_sim(
+            Banner::default(),
+            GoalKind::All,
+            vec![part(Color::Red, 1, PartTarget::Focus), either],
+        );
+        assert_eq!(sim.goal_data.group_parents, vec![0, 0]);
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Blue), 0));
+        assert_eq!(sim.goal_data.color_needed, [true, false, false, false]);
+        assert!(!sim.goal_data.is_met());
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Red), 0));
+        assert!(sim.goal_data.is_met());
+
+        // Either red, or both blue and green.
+        let both = GoalNode::Group(GoalGroup {
+            kind: GoalKind::All,
+            goals: vec![
+                part(Color::Blue, 1, PartTarget::Focus),
+                part(Color::Green, 1, PartTarget::Focus),
+            ],
+        });
+        let mut sim = custom_sim(
+            Banner::default(),
+            GoalKind::Any,
+            vec![part(Color::Red, 2, PartTarget::Focus), both],
+        );
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Blue), 0));
+        assert_eq!(sim.goal_data.color_needed, [true, false, true, false]);
+        assert!(sim.goal_data.add_unit((Pool::Focus, Color::Green), 0));
+        assert!(sim.goal_data.is_met());
+    }
+
+    #[test]
+    fn nonfocus_and_any_fivestar_parts_count_their_units() {
+        let mut sim = custom_sim(
+            Banner::default(),
+            GoalKind::All,
+            vec![
+                part(Color::Red, 1, PartTarget::NonFocus),
+                part(Color::Red, 2, PartTarget::AnyFivestar),
+            ],
+        );
+        let red_pool = sim.banner.pool_sizes(PoolSizes::default()).fivestar[Color::Red as usize];
+        assert_eq!(
+            sim.unit_count((Pool::Fivestar, Color::Red)),
+            red_pool as usize
+        );
+        assert_eq!(sim.goal_data.color_needed, [true; 4]);
+        assert!(sim.goal_data.may_progress((Pool::Focus, Color::Blue)));
+        assert!(!sim.goal_data.may_progress((Pool::Fourstar, Color::Red)));
+
+        // A non-focus red unit, which is a 5* unit as well.
+        assert!(sim.goal_data.add_unit((Pool::Fivestar, Color::Red), 0));
+        assert!(sim.goal_data.add_fivestar());
+        assert_eq!(sim.goal_data.fivestar_needed, vec![1]);
+        assert!(!sim.goal_data.is_met());
+        assert!(sim.goal_data.nonfocus_needed[Color::Red as usize].is_empty());
+
+        // Any other 5* unit finishes the goal.
+        assert!(!sim.goal_data.add_unit((Pool::Focus, Color::Blue), 0));
+        assert!(sim.goal_data.add_fivestar());
+        assert!(sim.goal_data.is_met());
+    }
+
+    #[test]
+    fn unreachable_goals_give_up() {
+        // Legendary banners have no non-focus 5* units, so this goal is
+        // rejected by `Goal::is_available` and can never be reached.
+        let mut banner = Banner::default();
+        banner.set_kind(BannerKind::Legendary);
+        let goal = Goal::Custom(CustomGoal {
+            kind: GoalKind::All,
+            goals: vec![GoalNode::Part(GoalPart {
+                unit_color: Color::Red,
+                num_copies: 1,
+                target: PartTarget::NonFocus,
+                unit: None,
+            })],
+            fourstar_merges: false,
+        });
+        assert!(!goal.is_available(&banner, PoolSizes::default()));
+        let mut sim = Sim::new(banner, goal, 0);
+        assert!(sim.roll_until_goal() >= MAX_ORBS);
+        let (spent, reached) = sim.roll_within_budget(1000);
+        assert!(!reached);
+        assert!(spent > 1000 - Sim::orb_cost(1));
+    }
+
+    #[test]
+    fn high_pity_with_fourstar_focus() {
+        // The 4* focus rate is left alone by the rate increases, so the 5*
+        // rates top out well short of 100% and the pity count can keep
+        // climbing past the last increase.
+        let banner = Banner {
+            kind: BannerKind::Custom,
+            starting_rates: [1.0, 0.0, 90.0, 5.0, 4.0, 0.0],
+            fourstar_focus_sizes: [1, 0, 0, 0],
+            ..Banner::default()
+        };
+        assert!(banner.follows_kind_rules() && banner.has_valid_rates());
+        let goal = Goal::Custom(CustomGoal {
+            kind: GoalKind::All,
+            goals: vec![GoalNode::Part(GoalPart {
+                unit_color: Color::Blue,
+                num_copies: 11,
+                target: PartTarget::Focus,
+                unit: None,
+            })],
+            fourstar_merges: false,
+        });
+        assert!(goal.is_available(&banner, PoolSizes::default()));
+        let mut sim = Sim::new(banner, goal, 0);
+        sim.set_progress(Progress {
+            pity_count: 124,
+            ..Progress::default()
+        });
+        for _ in 0..20 {
+            assert!(sim.roll_until_goal() > 0);
+        }
+    }
+
+    #[test]
+    fn rate_increases_stop_at_the_lower_rates() {
+        let banner = Banner {
+            kind: BannerKind::Custom,
+            starting_rates: [3.0, 3.0, 0.0, 1.0, 1.0, 92.0],
+            ..Banner::default()
+        };
+        assert!(banner.follows_kind_rules() && banner.has_valid_rates());
+        let sim = Sim::new(banner, Goal::default(), 0);
+        for pity_incr in 0..26 {
+            let probabilities = sim.probabilities(pity_incr);
+            assert!(probabilities.iter().all(|&rate| rate >= -1e-4));
+            assert!((probabilities.iter().sum::<f32>() - 100.0).abs() < 0.01);
+            let total: f64 = sim
+                .sample_probabilities(pity_incr, false)
+                .iter()
+                .map(|&(_, probability)| probability)
+                .sum();
+            assert!((total - 1.0).abs() < 1e-6);
+        }
+        // The whole 2% is used up after four increases.
+        let capped = sim.probabilities(4);
+        assert_eq!(capped, sim.probabilities(10));
+        assert!((capped[Pool::Focus as usize] - 4.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn same_seed_gives_same_results() {
+        let goal = Goal::Preset(GoalPreset::AnyFocus, 1);
+        let mut first = Sim::new(Banner::default(), goal.clone(), 0);
+        let mut second = Sim::new(Banner::default(), goal, 0);
+        let mut first_data = Counter::default();
+        let mut second_data = Counter::default();
+        first.run_chunk(7, 3, 200, &mut first_data, &mut Counter::default());
+        second.run_chunk(7, 3, 200, &mut second_data, &mut Counter::default());
+        assert_eq!(*first_data, *second_data);
+    }
+}
@@ -0,0 +1,313 @@
+//! Planning for several banners in a row that share one supply of orbs, where
+//! whatever one banner doesn't use is left over for the next.
+
+use super::*;
+
+use std::collections::HashMap;
+
+/// Number of plans that `optimize_limits` returns, best first.
+pub const PLANS_SHOWN: usize = 5;
+
+/// One banner of a campaign.
+#[derive(Debug, Clone)]
+struct Stage {
+    sim: Sim,
+    /// Orbs gained right before this banner starts.
+    income: u32,
+    /// The most orbs to spend on this banner before giving up on it, if any.
+    limit: Option<u32>,
+}
+
+/// A sequence of banners, each with its own goal, that are summoned on in
+/// order from a shared orb budget. Each banner is summoned on until its goal
+/// is reached, the orbs run out or its stop-loss limit is reached.
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    stages: Vec<Stage>,
+    starting_orbs: u32,
+}
+
+/// The data gathered by simulating a campaign.
+#[derive(Default, Debug, Clone)]
+pub struct CampaignResults {
+    /// The number of campaigns simulated.
+    pub samples: u32,
+    /// The number of simulations that reached the goal of each banner.
+    pub goals_reached: Vec<u32>,
+    /// The number of simulations that reached every goal.
+    pub all_reached: u32,
+    /// The number of orbs that each simulation had left at the end.
+    pub orbs_left: Counter,
+}
+
+impl Campaign {
+    /// Creates a campaign with no banners and the given number of orbs to
+    /// start with.
+    pub fn new(starting_orbs: u32) -> Self {
+        Campaign {
+            stages: vec![],
+            starting_orbs,
+        }
+    }
+
+    /// Adds a banner after the ones already in the campaign. `sim` holds the
+    /// banner, goal and everything else about how to summon on it, and
+    /// `income` is the number of orbs gained between the previous banner and
+    /// this one.
+    pub fn add_banner(&mut self, sim: Sim, income: u32) {
+        self.stages.push(Stage {
+            sim,
+            income,
+            limit: None,
+        });
+    }
+
+    /// Sets the most orbs to spend on the banner at `index` before moving on,
+    /// saving the rest for the banners after it. A limit of 0 skips the
+    /// banner.
+    pub fn set_limit(&mut self, index: usize, limit: Option<u32>) {
+        self.stages[index].limit = limit;
+    }
+
+    /// The stop-loss limit of each banner.
+    pub fn limits(&self) -> Vec<Option<u32>> {
+        self.stages.iter().map(|stage| stage.limit).collect()
+    }
+
+    /// The total number of orbs that the campaign has to spend, counting the
+    /// income before every banner.
+    pub fn total_orbs(&self) -> u32 {
+        self.starting_orbs + self.stages.iter().map(|stage| stage.income).sum::<u32>()
+    }
+
+    /// The number of banners in the campaign.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Whether there are no banners in the campaign yet.
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Runs the simulations for chunk number `chunk` of the run with the given
+    /// seed, adding the results to `results`. Only the first `samples` of the
+    /// chunk's simulations are run, which should be at most `CHUNK_SIZE`.
+    pub fn run_chunk(
+        &mut self,
+        seed: u64,
+        chunk: u64,
+        samples: u32,
+        results: &mut CampaignResults,
+    ) {
+        debug_assert!(samples <= CHUNK_SIZE);
+        results.goals_reached.resize(self.stages.len(), 0);
+        // Each banner gets its own stream of random numbers, so that changing
+        // the settings of one banner doesn't shuffle the luck on the others.
+        let chunk_seed = chunk_seed(seed, chunk);
+        for (index, stage) in self.stages.iter_mut().enumerate() {
+            stage.sim.reseed(splitmix64(chunk_seed ^ index as u64));
+        }
+        for _ in 0..samples {
+            let mut orbs = self.starting_orbs;
+            let mut all_reached = true;
+            for (index, stage) in self.stages.iter_mut().enumerate() {
+                orbs += stage.income;
+                let available = stage.limit.map_or(orbs, |limit| limit.min(orbs));
+                let (spent, reached) = stage.sim.roll_within_budget(available);
+                orbs -= spent;
+                if reached {
+                    results.goals_reached[index] += 1;
+                } else {
+                    all_reached = false;
+                }
+            }
+            if all_reached {
+                results.all_reached += 1;
+            }
+            results.orbs_left[orbs] += 1;
+            results.samples += 1;
+        }
+    }
+}
+
+impl CampaignResults {
+    /// The fraction of simulations that reached the goal of the banner at
+    /// `index`.
+    pub fn success(&self, index: usize) -> f64 {
+        self.fraction(self.goals_reached.get(index).copied().unwrap_or(0))
+    }
+
+    /// The fraction of simulations that reached every goal.
+    pub fn joint_success(&self) -> f64 {
+        self.fraction(self.all_reached)
+    }
+
+    /// The sum of each goal's chance of success times its weight. Goals
+    /// without a weight count once.
+    pub fn score(&self, weights: &[f64]) -> f64 {
+        (0..self.goals_reached.len())
+            .map(|index| weights.get(index).copied().unwrap_or(1.0) * self.success(index))
+            .sum()
+    }
+
+    /// Adds the results of another run of the same campaign.
+    pub fn merge(&mut self, other: &CampaignResults) {
+        if self.goals_reached.len() < other.goals_reached.len() {
+            self.goals_reached.resize(other.goals_reached.len(), 0);
+        }
+        for (total, &count) in self.goals_reached.iter_mut().zip(&other.goals_reached) {
+            *total += count;
+        }
+        self.samples += other.samples;
+        self.all_reached += other.all_reached;
+        self.orbs_left.merge(&other.orbs_left);
+    }
+
+    fn fraction(&self, count: u32) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            count as f64 / self.samples as f64
+        }
+    }
+}
+
+/// A choice of stop-loss limits for a campaign, along with how it did.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// The most orbs to spend on each banner, or `None` to keep going until
+    /// the goal is reached or the orbs run out.
+    pub limits: Vec<Option<u32>>,
+    /// The weighted score of the goals.
+    pub score: f64,
+    pub results: CampaignResults,
+}
+
+/// Searches for the stop-loss limits that give the campaign the best weighted
+/// score of its goals, then returns the best plans that it found, best first.
+/// Each banner's limit is a multiple of `step` orbs or no limit at all, except
+/// for the last banner, which keeps its limit since there's nothing after it
+/// to save orbs for.
+/// `evaluate` simulates a campaign with some limits set, and should use the
+/// same seed and number of samples every time so that the plans are compared
+/// on the same luck.
+///
+/// The search changes one banner's limit at a time, keeping the change that
+/// helps the most, until no single change helps. Trying every combination
+/// would take far too long with more than a couple of banners.
+pub fn optimize_limits(
+    campaign: &Campaign,
+    weights: &[f64],
+    step: u32,
+    mut evaluate: impl FnMut(&Campaign) -> CampaignResults,
+) -> Vec<Plan> {
+    let total_orbs = campaign.total_orbs();
+    let mut candidates = vec![None];
+    candidates.extend(
+        (0..)
+            .map(|multiple| multiple * step.max(1))
+            .take_while(|&limit| limit < total_orbs)
+            .map(Some),
+    );
+
+    let mut plans = HashMap::new();
+    let mut plan_score = |limits: &Vec<Option<u32>>| {
+        plans
+            .entry(limits.clone())
+            .or_insert_with(|| {
+                let mut campaign = campaign.clone();
+                for (index, &limit) in limits.iter().enumerate() {
+                    campaign.set_limit(index, limit);
+                }
+                let results = evaluate(&campaign);
+                Plan {
+                    limits: limits.clone(),
+                    score: results.score(weights),
+                    results,
+                }
+            })
+            .score
+    };
+
+    let mut best = campaign.limits();
+    let mut best_score = plan_score(&best);
+    loop {
+        let mut next = best.clone();
+        for index in 0..best.len().saturating_sub(1) {
+            for &limit in &candidates {
+                let mut limits = best.clone();
+                limits[index] = limit;
+                let score = plan_score(&limits);
+                if score > best_score {
+                    best_score = score;
+                    next = limits;
+                }
+            }
+        }
+        if next == best {
+            break;
+        }
+        best = next;
+    }
+
+    let mut plans = plans.into_values().collect::<Vec<_>>();
+    plans.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap()
+            .then_with(|| a.limits.cmp(&b.limits))
+    });
+    plans.truncate(PLANS_SHOWN);
+    plans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::goal::GoalPreset;
+
+    fn evaluate(campaign: &Campaign) -> CampaignResults {
+        let mut campaign = campaign.clone();
+        let mut results = CampaignResults::default();
+        campaign.run_chunk(5, 0, 300, &mut results);
+        results
+    }
+
+    #[test]
+    fn optimized_limits_stay_within_budget() {
+        let mut campaign = Campaign::new(150);
+        for (preset, income) in [
+            (GoalPreset::AllFocus, 0),
+            (GoalPreset::RedFocus, 20),
+            (GoalPreset::AnyFocus, 30),
+        ] {
+            campaign.add_banner(
+                Sim::new(Banner::default(), Goal::Preset(preset, 1), 0),
+                income,
+            );
+        }
+        campaign.set_limit(2, Some(40));
+        let total_orbs = campaign.total_orbs();
+        let step = 25;
+        let weights = [1.0, 2.0, 1.0];
+
+        let plans = optimize_limits(&campaign, &weights, step, evaluate);
+        assert!(!plans.is_empty() && plans.len() <= PLANS_SHOWN);
+        for plan in &plans {
+            assert_eq!(plan.limits.len(), campaign.len());
+            // The last banner isn't searched, so it keeps its limit.
+            assert_eq!(plan.limits[2], Some(40));
+            for limit in plan.limits[..2].iter().flatten() {
+                assert!(*limit < total_orbs);
+                assert_eq!(limit % step, 0);
+            }
+            assert!((plan.score - plan.results.score(&weights)).abs() < 1e-9);
+        }
+        assert!(plans.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        // The search starts from the campaign's own limits, so it can only
+        // do better than them.
+        assert!(plans[0].score >= evaluate(&campaign).score(&weights));
+    }
+}
@@ -0,0 +1,447 @@
+use super::*;
+
+use std::collections::HashMap;
+
+/// Calculates the exact probability of each possible number of orbs being
+/// needed to reach the goal with the settings of `sim`, instead of estimating
+/// it by sampling. Stops once less than `tolerance` of the probability is left
+/// unaccounted for, since the number of orbs needed has no upper limit.
+pub fn orb_distribution(sim: &Sim, tolerance: f64) -> Counter<f64> {
+    Solver::new(sim.clone()).solve(tolerance)
+}
+
+/// Paths through the states that are less likely than this are dropped instead
+/// of followed. There are a huge number of them that add up to almost nothing,
+/// so following them would take much longer without changing the results.
+const NEGLIGIBLE_PROBABILITY: f64 = 1e-13;
+
+/// The state of a sequence of summoning sessions between two sessions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct RunState {
+    pity_count: u32,
+    focus_charges: u32,
+    spark_progress: u32,
+    goal: usize,
+}
+
+/// Everything that the results of a single session depend on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct SessionKey {
+    pity_incr: u32,
+    focus_charge_active: bool,
+    /// Number of summons until the next spark, or 6 if it can't happen during
+    /// this session. Later sparks in the same session follow every threshold
+    /// summons after it.
+    summons_until_spark: u32,
+    goal: usize,
+}
+
+/// A session that has had some of its stones evaluated.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PartialSession {
+    chosen_count: u32,
+    off_color_count: u32,
+    got_focus: bool,
+    nonfocus_count: u32,
+    /// The pool of the first stone of the strategy's fallback color, while
+    /// nothing has been chosen.
+    held: Option<Pool>,
+    goal_data: GoalData,
+}
+
+/// One of the possible results of a session.
+#[derive(Copy, Clone, Debug)]
+struct SessionOutcome {
+    chosen_count: u32,
+    got_focus: bool,
+    nonfocus_count: u32,
+    /// The remaining goal after the session, or `None` if it was reached.
+    goal: Option<usize>,
+    probability: f64,
+}
+
+/// A possible move from one run state to another.
+#[derive(Copy, Clone, Debug)]
+struct Transition {
+    orb_cost: u32,
+    /// The index of the next state, or `None` if the goal was reached.
+    next: Option<usize>,
+    probability: f64,
+}
+
+/// Walks every reachable state of the simulation, using the same rules as
+/// `Sim` but tracking probabilities instead of picking random outcomes.
+struct Solver {
+    sim: Sim,
+    /// Every distinct goal state seen so far, so that states can refer to them
+    /// by index.
+    goals: Vec<GoalData>,
+    goal_ids: HashMap<GoalData, usize>,
+    sessions: HashMap<SessionKey, Vec<SessionOutcome>>,
+    /// Every distinct run state seen so far, along with the transitions out
+    /// of it once they have been needed.
+    states: Vec<(RunState, Option<Vec<Transition>>)>,
+    state_ids: HashMap<RunState, usize>,
+}
+
+impl Solver {
+    /// Creates a solver that follows the rules of `sim`, which is only used
+    /// for its settings and never for sampling.
+    fn new(sim: Sim) -> Self {
+        Solver {
+            sim,
+            goals: vec![],
+            goal_ids: HashMap::new(),
+            sessions: HashMap::new(),
+            states: vec![],
+            state_ids: HashMap::new(),
+        }
+    }
+
+    fn solve(&mut self, tolerance: f64) -> Counter<f64> {
+        let mut results = Counter::default();
+
+        self.sim.init_goal_data();
+        if self.sim.goal_data.is_met() {
+            results[0] = 1.0;
+            return results;
+        }
+        let goal = self.goal_id(self.sim.goal_data.clone());
+        let (pity_count, focus_charges) = self.sim.starting_pity();
+        let spark_progress = match self.sim.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => self.sim.progress.spark_count % threshold,
+            _ => 0,
+        };
+        let start = self.state_id(RunState {
+            pity_count,
+            focus_charges,
+            spark_progress,
+            goal,
+        });
+
+        // A session never costs more than 20 orbs, so only the states for the
+        // next 21 orb counts need to be kept around at any time. Each one
+        // holds the probability of every state, along with a list of which
+        // of those are nonzero.
+        let mut pending = vec![(vec![], vec![]); 21];
+        add_probability(&mut pending[0], start, 1.0);
+        let mut remaining = 1.0;
+        let mut orb_count = 0;
+
+        while remaining > tolerance
+            && orb_count < MAX_ORBS
+            && pending.iter().any(|(_, states)| !states.is_empty())
+        {
+            let slot = orb_count as usize % 21;
+            let states = std::mem::take(&mut pending[slot].1);
+            for state in states {
+                let probability = std::mem::replace(&mut pending[slot].0[state], 0.0);
+                for transition in self.transitions(state) {
+                    let probability = probability * transition.probability;
+                    let orbs = orb_count + transition.orb_cost;
+                    if probability < NEGLIGIBLE_PROBABILITY {
+                        continue;
+                    }
+                    if let Some(next) = transition.next {
+                        add_probability(&mut pending[orbs as usize % 21], next, probability);
+                    } else {
+                        results[orbs] += probability;
+                        remaining -= probability;
+                    }
+                }
+            }
+            orb_count += 1;
+        }
+
+        results
+    }
+
+    /// Finds the transitions out of the given state, calculating them if they
+    /// haven't been needed before.
+    fn transitions(&mut self, state: usize) -> &[Transition] {
+        if self.states[state].1.is_none() {
+            let transitions = self.calculate_transitions(self.states[state].0);
+            self.states[state].1 = Some(transitions);
+        }
+        self.states[state].1.as_ref().unwrap()
+    }
+
+    /// Converts the possible results of a session into moves between states.
+    fn calculate_transitions(&mut self, run_state: RunState) -> Vec<Transition> {
+        let key = self.session_key(run_state);
+        if !self.sessions.contains_key(&key) {
+            let outcomes = self.session_outcomes(key);
+            self.sessions.insert(key, outcomes);
+        }
+        let mut transitions: Vec<Transition> = vec![];
+        for outcome in self.sessions[&key].clone() {
+            let orb_cost = Sim::orb_cost(outcome.chosen_count);
+            let next = outcome
+                .goal
+                .map(|goal| self.state_id(self.next_state(run_state, &outcome, goal)));
+            // Different session results often lead to the same state, like
+            // when a focus unit resets the pity rate no matter what else was
+            // pulled, so combine them to save work later.
+            if let Some(transition) = transitions
+                .iter_mut()
+                .find(|transition| transition.orb_cost == orb_cost && transition.next == next)
+            {
+                transition.probability += outcome.probability;
+            } else {
+                transitions.push(Transition {
+                    orb_cost,
+                    next,
+                    probability: outcome.probability,
+                });
+            }
+        }
+        transitions
+    }
+
+    /// Finds the index for the run state, adding it if it's new.
+    fn state_id(&mut self, state: RunState) -> usize {
+        if let Some(&id) = self.state_ids.get(&state) {
+            return id;
+        }
+        self.states.push((state, None));
+        self.state_ids.insert(state, self.states.len() - 1);
+        self.states.len() - 1
+    }
+
+    /// Finds the index for the goal state, adding it if it's new.
+    fn goal_id(&mut self, goal_data: GoalData) -> usize {
+        if let Some(&id) = self.goal_ids.get(&goal_data) {
+            return id;
+        }
+        self.goals.push(goal_data.clone());
+        self.goal_ids.insert(goal_data, self.goals.len() - 1);
+        self.goals.len() - 1
+    }
+
+    fn session_key(&self, state: RunState) -> SessionKey {
+        let summons_until_spark = match self.sim.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => (threshold - state.spark_progress).min(6),
+            _ => 6,
+        };
+        SessionKey {
+            pity_incr: (state.pity_count / 5).min(25),
+            focus_charge_active: state.focus_charges == 3,
+            summons_until_spark,
+            goal: state.goal,
+        }
+    }
+
+    /// Applies the results of a session to the state before it.
+    fn next_state(&self, state: RunState, outcome: &SessionOutcome, goal: usize) -> RunState {
+        let mut pity_count = state.pity_count;
+        let mut focus_charges = state.focus_charges;
+        self.sim.update_pity(
+            &mut pity_count,
+            &mut focus_charges,
+            outcome.chosen_count,
+            outcome.got_focus,
+            outcome.nonfocus_count,
+        );
+        let spark_progress = match self.sim.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => {
+                (state.spark_progress + outcome.chosen_count) % threshold
+            }
+            _ => 0,
+        };
+        RunState {
+            pity_count,
+            focus_charges,
+            spark_progress,
+            goal,
+        }
+    }
+
+    /// Evaluates every way that a session can go, one stone at a time, using
+    /// the same choices as `Sim::session_select`.
+    fn session_outcomes(&mut self, key: SessionKey) -> Vec<SessionOutcome> {
+        let samples = self
+            .sim
+            .sample_probabilities(key.pity_incr, key.focus_charge_active);
+        // Without a spark, `summons_until_spark` is out of reach and the
+        // threshold is never used.
+        let spark_threshold = self.sim.banner.spark_threshold.unwrap_or(0).max(1);
+        let mut partials = HashMap::new();
+        partials.insert(
+            PartialSession {
+                chosen_count: 0,
+                off_color_count: 0,
+                got_focus: false,
+                nonfocus_count: 0,
+                held: None,
+                goal_data: self.goals[key.goal].clone(),
+            },
+            1.0,
+        );
+        let mut finished = HashMap::new();
+
+        for index in 0..5 {
+            let mut next = HashMap::new();
+            for (partial, probability) in partials {
+                for &(sample, sample_probability) in &samples {
+                    let probability = probability * sample_probability;
+                    let choice = Choice {
+                        color: sample.1,
+                        index,
+                        chosen_count: partial.chosen_count,
+                        off_color_count: partial.off_color_count,
+                        color_needed: partial.goal_data.color_needed,
+                        pity_incr: key.pity_incr,
+                    };
+                    let sample = match self.sim.chosen_stone(&choice, sample, partial.held) {
+                        Some(sample) => sample,
+                        None => {
+                            let mut passed = partial.clone();
+                            passed.held = self.sim.held_stone(&choice, sample, partial.held);
+                            *next.entry(passed).or_insert(0.0) += probability;
+                            continue;
+                        }
+                    };
+                    let is_needed = partial.goal_data.color_needed[sample.1 as usize];
+                    for (goal_data, unit_probability) in
+                        self.sim.pull_outcomes(&partial.goal_data, sample)
+                    {
+                        let mut pulled = PartialSession {
+                            chosen_count: partial.chosen_count + 1,
+                            off_color_count: partial.off_color_count
+                                + if is_needed { 0 } else { 1 },
+                            got_focus: partial.got_focus || sample.0 == Pool::Focus,
+                            nonfocus_count: partial.nonfocus_count
+                                + if sample.0 == Pool::Fivestar { 1 } else { 0 },
+                            held: None,
+                            goal_data,
+                        };
+                        if is_spark_summon(
+                            pulled.chosen_count,
+                            key.summons_until_spark,
+                            spark_threshold,
+                        ) {
+                            pulled.goal_data.spark(self.sim.banner.focus_sizes);
+                        }
+                        let target = if pulled.goal_data.is_met() {
+                            &mut finished
+                        } else {
+                            &mut next
+                        };
+                        *target.entry(pulled).or_insert(0.0) += probability * unit_probability;
+                    }
+                }
+            }
+            partials = next;
+        }
+
+        let mut outcomes = vec![];
+        for (partial, probability) in finished {
+            outcomes.push(SessionOutcome {
+                chosen_count: partial.chosen_count,
+                got_focus: partial.got_focus,
+                nonfocus_count: partial.nonfocus_count,
+                goal: None,
+                probability,
+            });
+        }
+        for (partial, probability) in partials {
+            outcomes.push(SessionOutcome {
+                chosen_count: partial.chosen_count,
+                got_focus: partial.got_focus,
+                nonfocus_count: partial.nonfocus_count,
+                goal: Some(self.goal_id(partial.goal_data)),
+                probability,
+            });
+        }
+        outcomes
+    }
+}
+
+/// Adds probability to a state in one of the pending orb counts.
+fn add_probability(pending: &mut (Vec<f64>, Vec<usize>), state: usize, probability: f64) {
+    let (probabilities, states) = pending;
+    if state >= probabilities.len() {
+        probabilities.resize(state + 1, 0.0);
+    }
+    if probabilities[state] == 0.0 {
+        states.push(state);
+    }
+    probabilities[state] += probability;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::goal::GoalPreset;
+    use crate::strategy::Strategy;
+
+    /// Checks that sampling the same settings gives about the same
+    /// distribution as the exact calculation.
+    fn assert_matches_samples(sim: Sim) {
+        let exact = orb_distribution(&sim, 1e-6);
+        let total: f64 = exact.iter().sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        let mut sampler = sim;
+        let mut samples = Counter::default();
+        for chunk in 0..20 {
+            sampler.run_chunk(1, chunk, CHUNK_SIZE, &mut samples, &mut Counter::default());
+        }
+        let (exact_mean, sampled_mean) = (stats::mean(&exact), stats::mean(&samples));
+        assert!(
+            (exact_mean - sampled_mean).abs() < exact_mean * 0.03,
+            "exact mean {} and sampled mean {} differ",
+            exact_mean,
+            sampled_mean,
+        );
+        for &orbs in &[20, 50, 100, 200] {
+            let (exact_cdf, sampled_cdf) = (stats::cdf(&exact, orbs), stats::cdf(&samples, orbs));
+            assert!(
+                (exact_cdf - sampled_cdf).abs() < 0.02,
+                "chances within {} orbs of {} and {} differ",
+                orbs,
+                exact_cdf,
+                sampled_cdf,
+            );
+        }
+    }
+
+    #[test]
+    fn matches_sampling() {
+        assert_matches_samples(Sim::new(
+            Banner::default(),
+            Goal::Preset(GoalPreset::RedFocus, 1),
+            0,
+        ));
+    }
+
+    #[test]
+    fn matches_sampling_with_several_sparks_per_session() {
+        for threshold in [2, 3] {
+            let banner = Banner {
+                spark_threshold: Some(threshold),
+                ..Banner::default()
+            };
+            let goal = Goal::Preset(GoalPreset::RedFocus, 4);
+            assert_matches_samples(Sim::new(banner, goal, 0));
+        }
+    }
+
+    #[test]
+    fn matches_sampling_with_progress() {
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::AnyFocus, 2), 0);
+        sim.set_progress(Progress {
+            pity_count: 60,
+            ..Progress::default()
+        });
+        assert_matches_samples(sim);
+    }
+
+    #[test]
+    fn matches_sampling_with_a_fallback_color() {
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::RedFocus, 1), 0);
+        sim.set_strategy(Strategy::OffColor(Color::Blue));
+        assert_matches_samples(sim);
+    }
+}
@@ -0,0 +1,888 @@
+use super::*;
+
+use std::collections::HashMap;
+use std::iter;
+
+use strum::IntoEnumIterator;
+
+/// What the optimal policy is chosen to achieve.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Objective {
+    /// Spend as few orbs as possible on average.
+    MinimizeOrbs,
+    /// Have the best chance of reaching the goal without spending more than
+    /// the given number of orbs.
+    MaximizeSuccess(u32),
+}
+
+/// The best policy for choosing stones, and how it compares to the choices
+/// that the simulator makes with its strategy.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub objective: Objective,
+    /// The average number of orbs spent, or the chance of success, when
+    /// following the optimal policy.
+    pub value: f64,
+    /// The same for the simulator's own choices.
+    pub current_value: f64,
+    /// Every situation at the start of a session where the optimal policy
+    /// does something other than pulling each stone of a color that the goal
+    /// needs, or a single stone when there are none of those.
+    pub decisions: Vec<Decision>,
+}
+
+impl Policy {
+    /// How much better the optimal policy does than the simulator's own
+    /// choices, in orbs saved or in added chance of success.
+    pub fn savings(&self) -> f64 {
+        match self.objective {
+            Objective::MinimizeOrbs => self.current_value - self.value,
+            Objective::MaximizeSuccess(_) => self.value - self.current_value,
+        }
+    }
+}
+
+/// What the optimal policy does with one circle of stones.
+#[derive(Clone, Debug)]
+pub struct Decision {
+    /// Orbs left to spend, if the policy is for a budget.
+    pub orbs_left: Option<u32>,
+    pub pity_count: u32,
+    pub focus_charges: u32,
+    /// Number of summons made towards the next spark. This is always 0 when
+    /// there aren't enough orbs left to reach the next spark.
+    pub spark_count: u32,
+    /// Copies still needed of each targeted unit, by color.
+    pub copies_needed: [Vec<u8>; 4],
+    /// Number of stones of each color in the circle.
+    pub circle: [u8; 4],
+    /// Number of stones of each color to pull. If one of them turns out to be
+    /// a 5* unit, the rest of the session is decided again from there.
+    pub pulls: [u8; 4],
+}
+
+/// Finds the policy for choosing stones that best meets `objective` with the
+/// banner, goal and progress of `sim`. Stones are chosen one at a time,
+/// knowing the colors left in the circle and the results of the stones
+/// already pulled. The simulator's own choices are evaluated with
+/// `exact::orb_distribution` for comparison.
+pub fn solve(sim: &Sim, objective: Objective) -> Policy {
+    let mut solver = Solver::new(sim.clone(), objective);
+    let value = solver.solve();
+    let current = exact::orb_distribution(sim, 1e-9);
+    let current_value = match objective {
+        Objective::MinimizeOrbs => stats::mean(&current),
+        Objective::MaximizeSuccess(budget) => stats::cdf(&current, budget),
+    };
+    Policy {
+        objective,
+        value,
+        current_value,
+        decisions: solver.decisions,
+    }
+}
+
+/// Choices that beat the usual one by less than this are treated as ties, so
+/// that rounding errors don't show up as decisions.
+const TIE_TOLERANCE: f64 = 1e-7;
+
+/// Average orb counts are refined until no state changes by more than this.
+const CONVERGENCE: f64 = 1e-9;
+
+/// The choice to end the session instead of pulling another stone. The other
+/// choices are the colors of the stone to pull.
+const STOP: u8 = 4;
+
+/// Number of pity counts that the solver keeps apart. Counts can't go past
+/// this once the 5* rates reach 100%, except on banners with a 4* focus unit,
+/// where higher counts are rare enough to be lumped in with the highest one.
+const PITY_STATES: u32 = MAX_PITY_COUNT + 5;
+
+/// The state of a sequence of summoning sessions between two sessions, apart
+/// from the goal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct RunState {
+    pity_count: u32,
+    focus_charges: u32,
+    spark_progress: u32,
+}
+
+/// How a session ended, apart from the goal.
+#[derive(Copy, Clone, Debug)]
+struct SessionEnd {
+    chosen_count: u32,
+    got_focus: bool,
+    nonfocus_count: u32,
+}
+
+/// A goal state, along with every state that it can lead to.
+struct GoalInfo {
+    data: GoalData,
+    met: bool,
+    /// The goal after pulling each color of stone from each pool, with the
+    /// probability of each.
+    pulls: [[Vec<(usize, f64)>; 6]; 4],
+    /// The goal after a spark.
+    spark: usize,
+    /// Every unmet goal that can be reached within a single session,
+    /// including this one.
+    session_goals: Vec<usize>,
+}
+
+/// The chances of seeing each kind of stone at one pity rate.
+struct Rates {
+    /// Every mix of colors that a circle can have, with its probability.
+    circles: Vec<([u8; 4], f64)>,
+    /// The chance of a stone of each color being from each pool.
+    pools: [[f64; 6]; 4],
+}
+
+/// The settings that don't change while solving.
+struct Model {
+    sim: Sim,
+    objective: Objective,
+    goals: Vec<GoalInfo>,
+    /// Rates for each number of pity increases, without and with a focus
+    /// charge active.
+    rates: Vec<[Rates; 2]>,
+    /// Number of distinct focus charge and spark progress counts.
+    charge_states: u32,
+    spark_states: u32,
+}
+
+/// The best choices within the session currently being solved. Entries are
+/// marked with the session that they belong to, so that the storage can be
+/// reused without clearing it.
+struct Memo {
+    session: u32,
+    /// Goals seen in this session, so that entries can refer to them by
+    /// position.
+    goals: Vec<usize>,
+    entries: Vec<(u32, f64, u8)>,
+    /// Numbers each possible set of stones left in the circle, indexed by
+    /// the count of each color in base 6.
+    remaining_ids: Vec<usize>,
+}
+
+/// Everything that the choices within one session depend on.
+struct Session<'a> {
+    goals: &'a [GoalInfo],
+    pools: &'a [[f64; 6]; 4],
+    objective: Objective,
+    summons_until_spark: u32,
+    /// Number of summons between sparks, for sessions that can spark more
+    /// than once.
+    spark_threshold: u32,
+    /// The most stones that can be pulled in the session.
+    max_pulls: u32,
+    /// The goals that the session can end with, and the scores of ending the
+    /// session in each way with each of them, as laid out by `session_ends`.
+    session_goals: &'a [usize],
+    ends: &'a [f64],
+}
+
+/// Works out the best choice in every state. Both objectives are solved by
+/// maximizing a score, which is the chance of success within a budget or the
+/// negative of the average number of orbs spent.
+struct Solver {
+    model: Model,
+    start_goal: usize,
+    start: RunState,
+    memo: Memo,
+    /// Scores of each state for each unmet goal, when minimizing orbs.
+    values: Vec<Vec<f64>>,
+    /// Scores of each number of orbs left, goal and state, when maximizing
+    /// success.
+    budget_values: HashMap<(u32, usize, usize), f64>,
+    decisions: Vec<Decision>,
+}
+
+impl Solver {
+    fn new(mut sim: Sim, objective: Objective) -> Self {
+        sim.init_goal_data();
+        let (pity_count, focus_charges) = sim.starting_pity();
+        let (spark_states, spark_progress) = match sim.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => (threshold, sim.progress.spark_count % threshold),
+            _ => (1, 0),
+        };
+        let start = RunState {
+            pity_count,
+            focus_charges,
+            spark_progress,
+        };
+        let start_data = sim.goal_data.clone();
+        let mut model = Model {
+            charge_states: if sim.banner.focus_charges { 4 } else { 1 },
+            spark_states,
+            rates: (0..26)
+                .map(|pity_incr| [sim.rates(pity_incr, false), sim.rates(pity_incr, true)])
+                .collect(),
+            sim,
+            objective,
+            goals: vec![],
+        };
+        model.add_goals(start_data);
+        Solver {
+            model,
+            start_goal: 0,
+            start,
+            memo: Memo::new(),
+            values: vec![],
+            budget_values: HashMap::new(),
+            decisions: vec![],
+        }
+    }
+
+    /// Finds the value of the optimal policy from the starting state.
+    fn solve(&mut self) -> f64 {
+        if self.model.goals[self.start_goal].met {
+            return match self.model.objective {
+                Objective::MinimizeOrbs => 0.0,
+                Objective::MaximizeSuccess(_) => 1.0,
+            };
+        }
+        match self.model.objective {
+            Objective::MinimizeOrbs => {
+                self.solve_orbs();
+                let index = self.model.index(self.start);
+                -self.values[self.start_goal][index]
+            }
+            Objective::MaximizeSuccess(budget) => {
+                let score = self.budget_score(budget, self.start_goal, self.start);
+                self.decisions.sort_by_key(|decision| {
+                    (
+                        decision.orbs_left,
+                        decision.spark_count,
+                        decision.focus_charges,
+                        decision.pity_count,
+                    )
+                });
+                score
+            }
+        }
+    }
+
+    /// Finds the score of every state for every unmet goal, from the goals
+    /// closest to being met upwards. The states for a single goal can lead
+    /// back to each other, so they are swept over until they stop changing.
+    fn solve_orbs(&mut self) {
+        let mut order = (0..self.model.goals.len())
+            .filter(|&goal| !self.model.goals[goal].met)
+            .collect::<Vec<_>>();
+        order.sort_by_key(|&goal| self.model.copies_needed(goal));
+        let state_count =
+            (PITY_STATES * self.model.charge_states * self.model.spark_states) as usize;
+        self.values = vec![vec![]; self.model.goals.len()];
+        for goal in order {
+            self.values[goal] = vec![0.0; state_count];
+            let first_decision = self.decisions.len();
+            loop {
+                // Decisions are recorded on every sweep, but only the ones
+                // from the sweep that shows the scores have settled are kept.
+                self.decisions.truncate(first_decision);
+                let mut max_change: f64 = 0.0;
+                // Going from the highest spark progress and pity count down
+                // means that most of the states that each one leads to have
+                // already been updated.
+                for index in (0..state_count).rev() {
+                    let state = self.model.state(index);
+                    let model = &self.model;
+                    let values = &self.values;
+                    let session_goals = &model.goals[goal].session_goals;
+                    let ends = session_ends(session_goals, 5, |end, next_goal| {
+                        let next = model.next_state(state, end);
+                        values[next_goal][model.index(next)]
+                            - Sim::orb_cost(end.chosen_count) as f64
+                    });
+                    let score = model.session_score(
+                        &mut self.memo,
+                        &mut self.decisions,
+                        goal,
+                        state,
+                        None,
+                        &ends,
+                    );
+                    max_change = max_change.max((score - self.values[goal][index]).abs());
+                    self.values[goal][index] = score;
+                }
+                // When every spark gets closer to the goal, states only lead
+                // to ones with more spark progress or to other goals, so a
+                // single sweep is enough.
+                let acyclic = self.model.spark_states > 1 && self.model.goals[goal].spark != goal;
+                if acyclic || max_change < CONVERGENCE {
+                    break;
+                }
+            }
+            self.decisions[first_decision..].sort_by_key(|decision| {
+                (
+                    decision.spark_count,
+                    decision.focus_charges,
+                    decision.pity_count,
+                )
+            });
+        }
+    }
+
+    /// Finds the score of a state with `orbs_left` to spend, along with the
+    /// scores of every state that it can lead to.
+    fn budget_score(&mut self, orbs_left: u32, goal: usize, mut state: RunState) -> f64 {
+        if orbs_left < Sim::orb_cost(1) {
+            return 0.0;
+        }
+        // Every summon costs at least 4 orbs, so when there aren't enough
+        // left to reach the next spark, the progress towards it doesn't
+        // matter and all of those states can share a score.
+        if state.spark_progress + orbs_left / 4 < self.model.spark_states {
+            state.spark_progress = 0;
+        }
+        let key = (orbs_left, goal, self.model.index(state));
+        if let Some(&score) = self.budget_values.get(&key) {
+            return score;
+        }
+
+        // The scores of every way that the session could end are found first,
+        // since finding them reuses the memo for other sessions.
+        let session_goals = self.model.goals[goal].session_goals.clone();
+        let ends = session_ends(
+            &session_goals,
+            max_pulls(Some(orbs_left)),
+            |end, next_goal| {
+                let next = self.model.next_state(state, end);
+                self.budget_score(orbs_left - Sim::orb_cost(end.chosen_count), next_goal, next)
+            },
+        );
+        let score = self.model.session_score(
+            &mut self.memo,
+            &mut self.decisions,
+            goal,
+            state,
+            Some(orbs_left),
+            &ends,
+        );
+        self.budget_values.insert(key, score);
+        score
+    }
+}
+
+impl Model {
+    /// Adds `start` and every goal that can be reached from it.
+    fn add_goals(&mut self, start: GoalData) {
+        let mut goal_ids = HashMap::new();
+        let mut add_goal = |goals: &mut Vec<GoalInfo>, data: GoalData| {
+            *goal_ids.entry(data.clone()).or_insert_with(|| {
+                // Goals that have been met stay that way, even through sparks.
+                goals.push(GoalInfo {
+                    met: data.is_met(),
+                    data,
+                    pulls: Default::default(),
+                    spark: goals.len(),
+                    session_goals: vec![],
+                });
+                goals.len() - 1
+            })
+        };
+        add_goal(&mut self.goals, start);
+        let mut next = 0;
+        while next < self.goals.len() {
+            let data = self.goals[next].data.clone();
+            if !data.is_met() {
+                for color in Color::iter() {
+                    for pool in 0..6 {
+                        let pool = Pool::try_from(pool).unwrap();
+                        let outcomes = self
+                            .sim
+                            .pull_outcomes(&data, (pool, color))
+                            .into_iter()
+                            .map(|(data, probability)| {
+                                (add_goal(&mut self.goals, data), probability)
+                            })
+                            .collect();
+                        self.goals[next].pulls[color as usize][pool as usize] = outcomes;
+                    }
+                }
+                let mut spark = data;
+                spark.spark(self.sim.banner.focus_sizes);
+                self.goals[next].spark = add_goal(&mut self.goals, spark);
+            }
+            next += 1;
+        }
+
+        for goal in 0..self.goals.len() {
+            if self.goals[goal].met {
+                continue;
+            }
+            let mut reached = vec![goal];
+            let mut frontier = vec![goal];
+            for _ in 0..5 {
+                let mut next_frontier = vec![];
+                for &from in &frontier {
+                    for outcomes in self.goals[from].pulls.iter().flatten() {
+                        for &(pulled, _) in outcomes {
+                            for to in [pulled, self.goals[pulled].spark] {
+                                if !self.goals[to].met && !reached.contains(&to) {
+                                    reached.push(to);
+                                    next_frontier.push(to);
+                                }
+                            }
+                        }
+                    }
+                }
+                frontier = next_frontier;
+            }
+            self.goals[goal].session_goals = reached;
+        }
+    }
+
+    /// The total number of copies still needed for the goal.
+    fn copies_needed(&self, goal: usize) -> u32 {
+        let data = &self.goals[goal].data;
+        data.copies_needed
+            .iter()
+            .chain(&data.nonfocus_needed)
+            .flatten()
+            .chain(&data.fivestar_needed)
+            .map(|&copies| copies as u32)
+            .sum()
+    }
+
+    /// The position of a state in the lists of scores.
+    fn index(&self, state: RunState) -> usize {
+        ((state.spark_progress * self.charge_states + state.focus_charges) * PITY_STATES
+            + state.pity_count) as usize
+    }
+
+    /// The state at a position in the lists of scores.
+    fn state(&self, index: usize) -> RunState {
+        let index = index as u32;
+        RunState {
+            pity_count: index % PITY_STATES,
+            focus_charges: index / PITY_STATES % self.charge_states,
+            spark_progress: index / PITY_STATES / self.charge_states,
+        }
+    }
+
+    /// Applies the results of a session to the state before it.
+    fn next_state(&self, state: RunState, end: SessionEnd) -> RunState {
+        let mut pity_count = state.pity_count;
+        let mut focus_charges = state.focus_charges;
+        self.sim.update_pity(
+            &mut pity_count,
+            &mut focus_charges,
+            end.chosen_count,
+            end.got_focus,
+            end.nonfocus_count,
+        );
+        RunState {
+            pity_count: pity_count.min(PITY_STATES - 1),
+            focus_charges,
+            spark_progress: (state.spark_progress + end.chosen_count) % self.spark_states,
+        }
+    }
+
+    /// Finds the score of starting a session in the given state, where `ends`
+    /// holds the scores of each way that the session can end without reaching
+    /// the goal. The situations where the best choice is unusual are added to
+    /// `decisions`.
+    fn session_score(
+        &self,
+        memo: &mut Memo,
+        decisions: &mut Vec<Decision>,
+        goal: usize,
+        state: RunState,
+        orbs_left: Option<u32>,
+        ends: &[f64],
+    ) -> f64 {
+        let pity_incr = (state.pity_count / 5).min(25);
+        let rates = &self.rates[pity_incr as usize][(state.focus_charges == 3) as usize];
+        let summons_until_spark = match self.sim.banner.spark_threshold {
+            Some(threshold) if threshold > 0 => threshold - state.spark_progress,
+            _ => u32::MAX,
+        };
+        let max_pulls = max_pulls(orbs_left);
+        let session = Session {
+            goals: &self.goals,
+            pools: &rates.pools,
+            objective: self.objective,
+            summons_until_spark,
+            spark_threshold: self.spark_states,
+            max_pulls,
+            session_goals: &self.goals[goal].session_goals,
+            ends,
+        };
+        memo.start();
+        let score = rates
+            .circles
+            .iter()
+            .map(|&(circle, probability)| {
+                probability * session.best(memo, circle, false, 0, goal).0
+            })
+            .sum();
+
+        let needed = self.goals[goal].data.color_needed;
+        for &(circle, _) in &rates.circles {
+            let pulls = session.plan(memo, circle, goal);
+            if !is_usual(circle, pulls, needed, max_pulls) {
+                decisions.push(Decision {
+                    orbs_left,
+                    pity_count: state.pity_count,
+                    focus_charges: state.focus_charges,
+                    spark_count: state.spark_progress,
+                    copies_needed: self.goals[goal].data.copies_needed.clone(),
+                    circle,
+                    pulls,
+                });
+            }
+        }
+        score
+    }
+}
+
+impl Memo {
+    /// The number of different sets of stones that can be left in a circle.
+    const REMAINING_COUNT: usize = 126;
+
+    fn new() -> Self {
+        let mut remaining_ids = vec![0; 6 * 6 * 6 * 6];
+        let mut next_id = 0;
+        for (code, id) in remaining_ids.iter_mut().enumerate() {
+            let total = (0..4)
+                .map(|place| code / 6usize.pow(place) % 6)
+                .sum::<usize>();
+            if total <= 5 {
+                *id = next_id;
+                next_id += 1;
+            }
+        }
+        debug_assert_eq!(next_id, Memo::REMAINING_COUNT);
+        Memo {
+            session: 0,
+            goals: vec![],
+            entries: vec![],
+            remaining_ids,
+        }
+    }
+
+    /// Starts on a new session, forgetting the choices for the last one.
+    fn start(&mut self) {
+        self.session += 1;
+        self.goals.clear();
+    }
+
+    /// Finds the position of the entry for a point in the session.
+    fn slot(
+        &mut self,
+        remaining: [u8; 4],
+        got_focus: bool,
+        nonfocus_count: u32,
+        goal: usize,
+    ) -> usize {
+        let goal = match self.goals.iter().position(|&seen| seen == goal) {
+            Some(position) => position,
+            None => {
+                self.goals.push(goal);
+                self.entries
+                    .resize(self.goals.len() * 7 * Memo::REMAINING_COUNT, (0, 0.0, STOP));
+                self.goals.len() - 1
+            }
+        };
+        let code = remaining
+            .iter()
+            .rev()
+            .fold(0, |code, &count| code * 6 + count as usize);
+        (goal * 7 + result_index(got_focus, nonfocus_count)) * Memo::REMAINING_COUNT
+            + self.remaining_ids[code]
+    }
+}
+
+impl Session<'_> {
+    /// The best score and choice with the stones in `remaining` left in the
+    /// circle, after pulling the rest with the given results.
+    fn best(
+        &self,
+        memo: &mut Memo,
+        remaining: [u8; 4],
+        got_focus: bool,
+        nonfocus_count: u32,
+        goal: usize,
+    ) -> (f64, u8) {
+        let slot = memo.slot(remaining, got_focus, nonfocus_count, goal);
+        let (session, score, choice) = memo.entries[slot];
+        if session == memo.session {
+            return (score, choice);
+        }
+
+        let chosen = 5 - remaining.iter().map(|&count| count as u32).sum::<u32>();
+        let needed = self.goals[goal].data.color_needed;
+        // The usual choice goes first so that it wins any ties.
+        let usual = (0..4)
+            .find(|&color| remaining[color] > 0 && needed[color])
+            .or_else(|| (0..4).find(|&color| chosen == 0 && remaining[color] > 0))
+            .map_or(STOP, |color| color as u8);
+        let mut best = (f64::NEG_INFINITY, STOP);
+        for choice in iter::once(usual).chain((0..=STOP).filter(|&choice| choice != usual)) {
+            let score = if choice == STOP {
+                if chosen == 0 {
+                    continue;
+                }
+                let end = SessionEnd {
+                    chosen_count: chosen,
+                    got_focus,
+                    nonfocus_count,
+                };
+                let position = self
+                    .session_goals
+                    .iter()
+                    .position(|&ended| ended == goal)
+                    .unwrap();
+                self.ends[end_index(end, position, self.session_goals.len())]
+            } else {
+                if remaining[choice as usize] == 0 || chosen >= self.max_pulls {
+                    continue;
+                }
+                self.pull_score(
+                    memo,
+                    remaining,
+                    got_focus,
+                    nonfocus_count,
+                    goal,
+                    choice as usize,
+                )
+            };
+            if score > best.0 + TIE_TOLERANCE {
+                best = (score, choice);
+            }
+        }
+        memo.entries[slot] = (memo.session, best.0, best.1);
+        best
+    }
+
+    /// The score of pulling a stone of the given color and then carrying on
+    /// with the best choices.
+    fn pull_score(
+        &self,
+        memo: &mut Memo,
+        mut remaining: [u8; 4],
+        got_focus: bool,
+        nonfocus_count: u32,
+        goal: usize,
+        color: usize,
+    ) -> f64 {
+        remaining[color] -= 1;
+        let chosen = 5 - remaining.iter().map(|&count| count as u32).sum::<u32>();
+        let mut score = 0.0;
+        for (pool, &pool_probability) in self.pools[color].iter().enumerate() {
+            if pool_probability == 0.0 {
+                continue;
+            }
+            let got_focus = got_focus || pool == Pool::Focus as usize;
+            let nonfocus_count = if got_focus {
+                0
+            } else {
+                nonfocus_count
+                    + if pool == Pool::Fivestar as usize {
+                        1
+                    } else {
+                        0
+                    }
+            };
+            for &(next_goal, probability) in &self.goals[goal].pulls[color][pool] {
+                let next_goal =
+                    if is_spark_summon(chosen, self.summons_until_spark, self.spark_threshold) {
+                        self.goals[next_goal].spark
+                    } else {
+                        next_goal
+                    };
+                let next_score = if self.goals[next_goal].met {
+                    match self.objective {
+                        Objective::MinimizeOrbs => -(Sim::orb_cost(chosen) as f64),
+                        Objective::MaximizeSuccess(_) => 1.0,
+                    }
+                } else {
+                    self.best(memo, remaining, got_focus, nonfocus_count, next_goal)
+                        .0
+                };
+                score += pool_probability * probability * next_score;
+            }
+        }
+        score
+    }
+
+    /// The stones pulled from `circle` as long as none of them turn out to be
+    /// 5* units.
+    fn plan(&self, memo: &mut Memo, circle: [u8; 4], goal: usize) -> [u8; 4] {
+        let mut remaining = circle;
+        let mut pulls = [0; 4];
+        loop {
+            let (_, choice) = self.best(memo, remaining, false, 0, goal);
+            if choice == STOP {
+                return pulls;
+            }
+            remaining[choice as usize] -= 1;
+            pulls[choice as usize] += 1;
+        }
+    }
+}
+
+impl Sim {
+    /// The chances of seeing each kind of stone in a session.
+    fn rates(&self, pity_incr: u32, focus_charge_active: bool) -> Rates {
+        let mut pools = [[0.0; 6]; 4];
+        for ((pool, color), probability) in
+            self.sample_probabilities(pity_incr, focus_charge_active)
+        {
+            pools[color as usize][pool as usize] = probability;
+        }
+        // 3* and 4* units have the same effect on everything, so they can be
+        // counted together.
+        for pools in pools.iter_mut() {
+            pools[Pool::Fourstar as usize] += pools[Pool::Threestar as usize];
+            pools[Pool::Threestar as usize] = 0.0;
+        }
+        let mut color_probabilities = [0.0; 4];
+        for (color_probability, pools) in color_probabilities.iter_mut().zip(pools.iter_mut()) {
+            *color_probability = pools.iter().sum();
+            if *color_probability > 0.0 {
+                for probability in pools.iter_mut() {
+                    *probability /= *color_probability;
+                }
+            }
+        }
+
+        let factorial = |n: u8| (1..=n as u32).product::<u32>() as f64;
+        let mut circles = vec![];
+        for red in 0..=5 {
+            for blue in 0..=5 - red {
+                for green in 0..=5 - red - blue {
+                    let circle = [red, blue, green, 5 - red - blue - green];
+                    let probability = circle
+                        .iter()
+                        .zip(&color_probabilities)
+                        .map(|(&count, &probability): (&u8, &f64)| {
+                            probability.powi(count as i32) / factorial(count)
+                        })
+                        .product::<f64>()
+                        * factorial(5);
+                    if probability > 0.0 {
+                        circles.push((circle, probability));
+                    }
+                }
+            }
+        }
+        Rates { circles, pools }
+    }
+}
+
+/// The most stones that can be pulled in a session with `orbs_left` to spend.
+fn max_pulls(orbs_left: Option<u32>) -> u32 {
+    match orbs_left {
+        Some(orbs_left) => (1..=5)
+            .take_while(|&count| Sim::orb_cost(count) <= orbs_left)
+            .last()
+            .unwrap_or(0),
+        None => 5,
+    }
+}
+
+/// Scores every way that a session with up to `max_pulls` stones can end
+/// without reaching the goal, ending with each of `session_goals`.
+fn session_ends(
+    session_goals: &[usize],
+    max_pulls: u32,
+    mut score: impl FnMut(SessionEnd, usize) -> f64,
+) -> Vec<f64> {
+    let mut ends = vec![0.0; 5 * 7 * session_goals.len()];
+    for chosen_count in 1..=max_pulls {
+        let results = (0..=chosen_count)
+            .map(|count| (false, count))
+            .chain(iter::once((true, 0)));
+        for (got_focus, nonfocus_count) in results {
+            let end = SessionEnd {
+                chosen_count,
+                got_focus,
+                nonfocus_count,
+            };
+            for (position, &goal) in session_goals.iter().enumerate() {
+                ends[end_index(end, position, session_goals.len())] = score(end, goal);
+            }
+        }
+    }
+    ends
+}
+
+/// The position of the score for ending a session in the given way, with the
+/// goal at `position` out of `goal_count`, in the list from `session_ends`.
+fn end_index(end: SessionEnd, position: usize, goal_count: usize) -> usize {
+    ((end.chosen_count as usize - 1) * 7 + result_index(end.got_focus, end.nonfocus_count))
+        * goal_count
+        + position
+}
+
+/// Combines whether a focus unit was pulled and the number of other 5* units
+/// pulled into a single index from 0 to 6. The count doesn't matter once a
+/// focus unit has been pulled.
+fn result_index(got_focus: bool, nonfocus_count: u32) -> usize {
+    if got_focus {
+        6
+    } else {
+        nonfocus_count as usize
+    }
+}
+
+/// Whether pulling `pulls` from `circle` is the usual choice of every stone of
+/// a needed color, or a single stone when there are none of those.
+fn is_usual(circle: [u8; 4], pulls: [u8; 4], needed: [bool; 4], max_pulls: u32) -> bool {
+    let needed_count: u32 = (0..4)
+        .filter(|&color| needed[color])
+        .map(|color| circle[color] as u32)
+        .sum();
+    let pulled_count: u32 = pulls.iter().map(|&count| count as u32).sum();
+    if needed_count == 0 {
+        pulled_count == 1
+    } else {
+        (0..4).all(|color| needed[color] || pulls[color] == 0)
+            && pulled_count == needed_count.min(max_pulls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::goal::GoalPreset;
+    use crate::strategy::Strategy;
+
+    /// A simulator that makes its choices with `Strategy::GoalColors`, on a
+    /// banner without sparks or focus charges so that there are few enough
+    /// situations for the solver to go through quickly.
+    fn goal_colors_sim(goal: Goal) -> Sim {
+        let banner = Banner {
+            spark_threshold: None,
+            focus_charges: false,
+            ..Banner::default()
+        };
+        let mut sim = Sim::new(banner, goal, 0);
+        sim.set_strategy(Strategy::GoalColors);
+        sim
+    }
+
+    #[test]
+    fn never_spends_more_than_goal_colors() {
+        let sim = goal_colors_sim(Goal::Preset(GoalPreset::RedFocus, 1));
+        let policy = solve(&sim, Objective::MinimizeOrbs);
+        assert!(policy.value > 0.0);
+        assert!(policy.value <= policy.current_value + 1e-6);
+        assert!(policy.savings() >= -1e-6);
+    }
+
+    #[test]
+    fn never_succeeds_less_than_goal_colors() {
+        for goal in [
+            Goal::Preset(GoalPreset::BlueFocus, 1),
+            Goal::Preset(GoalPreset::AllFocus, 1),
+        ] {
+            let policy = solve(&goal_colors_sim(goal), Objective::MaximizeSuccess(60));
+            assert!((0.0..=1.0).contains(&policy.value));
+            assert!(policy.value >= policy.current_value - 1e-6);
+        }
+    }
+}
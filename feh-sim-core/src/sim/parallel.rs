@@ -0,0 +1,200 @@
+//! Running simulations on multiple threads. Only available in native builds,
+//! since the browser doesn't have threads to spare.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::counter::Counter;
+use crate::sim::campaign::{Campaign, CampaignResults};
+use crate::sim::{Sim, CHUNK_SIZE};
+
+/// The data gathered by a run.
+#[derive(Default, Debug, Clone)]
+pub struct RunData {
+    /// The number of orbs that each simulation took to reach the goal.
+    pub orbs: Counter,
+    /// The number of goal copies that each simulation had gotten when it went
+    /// over the budget, if there is one.
+    pub budget_copies: Counter,
+}
+
+/// Runs `samples` simulations with copies of `sim` split across `threads`
+/// threads. The results depend only on the seed, not on the number of threads.
+pub fn run(sim: &Sim, seed: u64, samples: u64, threads: usize) -> RunData {
+    run_sims(sim, seed, threads, sample_limit(samples))
+}
+
+/// Runs simulations with copies of `sim` split across `threads` threads until
+/// `duration` has passed. The results are the same as a call to `run` with the
+/// same seed and the total number of samples that were completed.
+pub fn run_for(sim: &Sim, seed: u64, duration: Duration, threads: usize) -> RunData {
+    run_sims(sim, seed, threads, time_limit(duration))
+}
+
+/// Runs `samples` simulations of `campaign` split across `threads` threads.
+/// The results depend only on the seed, not on the number of threads.
+pub fn run_campaign(
+    campaign: &Campaign,
+    seed: u64,
+    samples: u64,
+    threads: usize,
+) -> CampaignResults {
+    run_campaigns(campaign, seed, threads, sample_limit(samples))
+}
+
+/// Runs simulations of `campaign` split across `threads` threads until
+/// `duration` has passed.
+pub fn run_campaign_for(
+    campaign: &Campaign,
+    seed: u64,
+    duration: Duration,
+    threads: usize,
+) -> CampaignResults {
+    run_campaigns(campaign, seed, threads, time_limit(duration))
+}
+
+/// Chunk sizes for a run of `samples` simulations in total.
+fn sample_limit(samples: u64) -> impl Fn(u64) -> Option<u32> + Sync {
+    let chunk_size = CHUNK_SIZE as u64;
+    move |chunk| {
+        let remaining = samples.saturating_sub(chunk * chunk_size);
+        if remaining > 0 {
+            Some(remaining.min(chunk_size) as u32)
+        } else {
+            None
+        }
+    }
+}
+
+/// Chunk sizes for a run that lasts for `duration`.
+fn time_limit(duration: Duration) -> impl Fn(u64) -> Option<u32> + Sync {
+    let start = Instant::now();
+    move |_| {
+        if start.elapsed() < duration {
+            Some(CHUNK_SIZE)
+        } else {
+            None
+        }
+    }
+}
+
+fn run_sims(
+    sim: &Sim,
+    seed: u64,
+    threads: usize,
+    chunk_samples: impl Fn(u64) -> Option<u32> + Sync,
+) -> RunData {
+    run_chunks(
+        sim,
+        threads,
+        chunk_samples,
+        |sim, chunk, samples, data: &mut RunData| {
+            sim.run_chunk(
+                seed,
+                chunk,
+                samples,
+                &mut data.orbs,
+                &mut data.budget_copies,
+            );
+        },
+        |data, thread_data| {
+            data.orbs.merge(&thread_data.orbs);
+            data.budget_copies.merge(&thread_data.budget_copies);
+        },
+    )
+}
+
+fn run_campaigns(
+    campaign: &Campaign,
+    seed: u64,
+    threads: usize,
+    chunk_samples: impl Fn(u64) -> Option<u32> + Sync,
+) -> CampaignResults {
+    run_chunks(
+        campaign,
+        threads,
+        chunk_samples,
+        |campaign, chunk, samples, results| campaign.run_chunk(seed, chunk, samples, results),
+        |results, thread_results| results.merge(&thread_results),
+    )
+}
+
+/// Runs chunks in order on each thread, each with its own copy of `runner`,
+/// until `chunk_samples` says to stop by returning `None`, then merges the
+/// results.
+fn run_chunks<R: Clone + Sync, D: Default + Send>(
+    runner: &R,
+    threads: usize,
+    chunk_samples: impl Fn(u64) -> Option<u32> + Sync,
+    run_chunk: impl Fn(&mut R, u64, u32, &mut D) + Sync,
+    merge: impl Fn(&mut D, D),
+) -> D {
+    // Handing out chunks under a lock means that once `chunk_samples` says to
+    // stop, no later chunk gets started, so the finished chunks are always
+    // the first however many.
+    let next_chunk = Mutex::new(0);
+    let claim_chunk = || {
+        let mut next_chunk = next_chunk.lock().unwrap();
+        let chunk = *next_chunk;
+        let samples = chunk_samples(chunk)?;
+        *next_chunk += 1;
+        Some((chunk, samples))
+    };
+
+    thread::scope(|scope| {
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut runner = runner.clone();
+                    let mut data = D::default();
+                    while let Some((chunk, samples)) = claim_chunk() {
+                        run_chunk(&mut runner, chunk, samples, &mut data);
+                    }
+                    data
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut data = D::default();
+        for handle in handles {
+            merge(&mut data, handle.join().unwrap());
+        }
+        data
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::goal::{Goal, GoalPreset};
+    use crate::Banner;
+
+    #[test]
+    fn thread_count_does_not_change_results() {
+        let mut sim = Sim::new(Banner::default(), Goal::Preset(GoalPreset::AnyFocus, 1), 0);
+        sim.set_budget(Some(50));
+        // Not a multiple of the chunk size, so the last chunk is partial.
+        let samples = 3 * CHUNK_SIZE as u64 + 10;
+        let single = run(&sim, 42, samples, 1);
+        let multiple = run(&sim, 42, samples, 4);
+        assert_eq!(single.orbs.iter().sum::<u32>() as u64, samples);
+        assert_eq!(*single.orbs, *multiple.orbs);
+        assert_eq!(*single.budget_copies, *multiple.budget_copies);
+    }
+
+    #[test]
+    fn thread_count_does_not_change_campaign_results() {
+        let goal = Goal::Preset(GoalPreset::AnyFocus, 1);
+        let mut campaign = Campaign::new(200);
+        campaign.add_banner(Sim::new(Banner::default(), goal.clone(), 0), 0);
+        campaign.add_banner(Sim::new(Banner::default(), goal, 0), 50);
+        let single = run_campaign(&campaign, 42, 2500, 1);
+        let multiple = run_campaign(&campaign, 42, 2500, 3);
+        assert_eq!(single.samples, 2500);
+        assert_eq!(single.goals_reached, multiple.goals_reached);
+        assert_eq!(single.all_reached, multiple.all_reached);
+        assert_eq!(*single.orbs_left, *multiple.orbs_left);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::counter::{Count, Counter};
+
+/// Calculates the given percentile of the data. `pct` is in the range [0.0, 1.0]
+pub fn percentile<T: Count>(data: &Counter<T>, pct: f32) -> u32 {
+    percentiles(data, &[pct])[0]
+}
+
+/// Calculates multiple percentiles in bulk. `pcts` must be sorted in ascending order
+/// with every value in the range [0.0, 1.0].
+pub fn percentiles<T: Count>(data: &Counter<T>, pcts: &[f32]) -> Vec<u32> {
+    debug_assert!(pcts.iter().all(|x| (0.0..=1.0).contains(x)));
+    debug_assert!((0..pcts.len() - 1).all(|idx| pcts[idx + 1] >= pcts[idx]));
+
+    let total: f64 = data.iter().map(|&x| x.into()).sum();
+    let mut results = vec![0; pcts.len()];
+
+    if total == 0.0 {
+        return results;
+    }
+
+    let mut accum_total = 0.0;
+    let mut out_idx = 0;
+    for value in 0..data.len() as u32 {
+        accum_total += data[value].into();
+        while out_idx < results.len() && accum_total / total > pcts[out_idx] as f64 {
+            results[out_idx] = value;
+            out_idx += 1;
+        }
+    }
+
+    if out_idx == results.len() {
+        return results;
+    }
+
+    // The remaining values in pcts are 100% (or close enough for rounding errors)
+    // if it didn't already finish, so grab the last non-zero value and fill the
+    // rest of the results.
+    if let Some(value) = data.iter().rposition(|&x| x > T::default()) {
+        for result in results.iter_mut().skip(out_idx) {
+            *result = value as u32;
+        }
+        return results;
+    }
+
+    // It would have returned early if all entries were zero, so the above search
+    // is guaranteed to find something and exit.
+    unreachable!()
+}
+
+/// Calculates the fraction of the data that is at most `value`, or zero if
+/// there is no data.
+pub fn cdf<T: Count>(data: &Counter<T>, value: u32) -> f64 {
+    let total: f64 = data.iter().map(|&x| x.into()).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let below: f64 = data
+        .iter()
+        .take((value as usize).saturating_add(1))
+        .map(|&x| x.into())
+        .sum();
+    below / total
+}
+
+/// Calculates the mean of the data, or zero if there is no data.
+pub fn mean<T: Count>(data: &Counter<T>) -> f64 {
+    let mut total = 0.0;
+    let mut weighted_total = 0.0;
+    for (value, &count) in data.iter().enumerate() {
+        let count: f64 = count.into();
+        total += count;
+        weighted_total += value as f64 * count;
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        weighted_total / total
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::goal::CustomGoal;
+use crate::Color;
+
+/// Everything that a strategy can see when deciding whether to pull a stone.
+/// Stones are decided on one at a time, in order.
+#[derive(Copy, Clone, Debug)]
+pub struct Choice {
+    /// The color of the stone.
+    pub color: Color,
+    /// The position of the stone in the session, from 0 to 4.
+    pub index: usize,
+    /// The number of stones pulled so far in the session.
+    pub chosen_count: u32,
+    /// The number of stones pulled so far in the session that were of a
+    /// color the goal didn't need.
+    pub off_color_count: u32,
+    /// Which colors could still make progress towards the goal.
+    pub color_needed: [bool; 4],
+    /// The number of times that the 5* rates have gone up by 0.5%.
+    pub pity_incr: u32,
+}
+
+impl Choice {
+    /// Whether the stone's color could make progress towards the goal.
+    pub fn is_needed(&self) -> bool {
+        self.color_needed[self.color as usize]
+    }
+}
+
+/// A policy for which stones to pull in each summoning session. A session
+/// always pulls at least one stone, so if a strategy passes on all five, one
+/// is pulled regardless: the first stone of the fallback color if there was
+/// one, or else the last stone.
+pub trait PullStrategy: fmt::Debug + Send + Sync {
+    /// Decides whether to pull the stone described by `choice`.
+    fn is_chosen(&self, choice: &Choice) -> bool;
+
+    /// The color to pull in a session where the strategy passes on every
+    /// stone, if a stone of that color shows up.
+    fn fallback_color(&self) -> Option<Color> {
+        None
+    }
+}
+
+/// The built-in strategies.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Strategy {
+    /// Pull every stone of a color that the goal needs, and an off-color stone
+    /// only when no needed color shows up, in which case it's the last stone.
+    #[default]
+    GoalColors,
+    /// Pull all five stones in every session.
+    FullCircle,
+    /// Pull only stones of the given color. When that color doesn't show up,
+    /// the last stone is still pulled even though it is a different color,
+    /// since every session pulls at least one.
+    SingleColor(Color),
+    /// Pull every stone of a color that the goal needs, plus one off-color
+    /// stone once there have been at least this many summons without the 5*
+    /// rates resetting.
+    ExtraAtHighPity(u32),
+    /// Pull every stone of a color that the goal needs, and when no needed
+    /// color shows up, a stone of the given color if there is one. None of the
+    /// off-color stones can help the goal, but their colors have different
+    /// chances of a 5* that resets the rates or adds a focus charge.
+    OffColor(Color),
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Strategy::GoalColors => write!(f, "Goal colors"),
+            Strategy::FullCircle => write!(f, "Full circle"),
+            Strategy::SingleColor(color) => write!(f, "Only {}", color),
+            Strategy::ExtraAtHighPity(summons) => {
+                write!(f, "Extra stone after {} summons", summons)
+            }
+            Strategy::OffColor(color) => write!(f, "Goal colors, else {}", color),
+        }
+    }
+}
+
+impl PullStrategy for Strategy {
+    fn is_chosen(&self, choice: &Choice) -> bool {
+        match *self {
+            Strategy::GoalColors => choice.is_needed(),
+            Strategy::FullCircle => true,
+            Strategy::SingleColor(color) => choice.color == color,
+            Strategy::ExtraAtHighPity(summons) => {
+                choice.is_needed()
+                    || (choice.pity_incr * 5 >= summons && choice.off_color_count == 0)
+            }
+            Strategy::OffColor(_) => choice.is_needed(),
+        }
+    }
+
+    fn fallback_color(&self) -> Option<Color> {
+        match *self {
+            Strategy::OffColor(color) => Some(color),
+            _ => None,
+        }
+    }
+}
+
+impl Strategy {
+    /// One of each kind of built-in strategy, with settings that suit the
+    /// goal, for comparing them against each other.
+    pub fn comparison_set(goal: &CustomGoal) -> Vec<Strategy> {
+        let color = goal
+            .parts()
+            .first()
+            .map_or(Color::Red, |part| part.unit_color);
+        let off_color = Color::iter()
+            .find(|&color| !goal.parts().iter().any(|part| part.unit_color == color))
+            .unwrap_or(Color::Colorless);
+        vec![
+            Strategy::GoalColors,
+            Strategy::FullCircle,
+            Strategy::SingleColor(color),
+            Strategy::ExtraAtHighPity(60),
+            Strategy::OffColor(off_color),
+        ]
+    }
+
+    /// Parses data from the representation used in query strings to share settings.
+    pub fn from_query_string(s: &str) -> Option<Self> {
+        let data = base64::decode(s).ok()?;
+        bincode::deserialize(&data).ok()
+    }
+
+    /// Converts to the representation used in query strings to share settings.
+    pub fn to_query_string(&self) -> String {
+        base64::encode(&bincode::serialize(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A red stone in a session with nothing chosen yet, for a goal that
+    /// needs only blue.
+    fn off_color_choice() -> Choice {
+        Choice {
+            color: Color::Red,
+            index: 0,
+            chosen_count: 0,
+            off_color_count: 0,
+            color_needed: [false, true, false, false],
+            pity_incr: 0,
+        }
+    }
+
+    #[test]
+    fn strategies_choose_their_stones() {
+        let off_color = off_color_choice();
+        let needed = Choice {
+            color: Color::Blue,
+            ..off_color
+        };
+        let high_pity = Choice {
+            pity_incr: 12,
+            ..off_color
+        };
+
+        assert!(Strategy::GoalColors.is_chosen(&needed));
+        assert!(!Strategy::GoalColors.is_chosen(&off_color));
+        assert!(Strategy::FullCircle.is_chosen(&off_color));
+        assert!(Strategy::SingleColor(Color::Red).is_chosen(&off_color));
+        assert!(!Strategy::SingleColor(Color::Red).is_chosen(&needed));
+        assert!(!Strategy::ExtraAtHighPity(60).is_chosen(&off_color));
+        assert!(Strategy::ExtraAtHighPity(60).is_chosen(&high_pity));
+        assert!(!Strategy::ExtraAtHighPity(60).is_chosen(&Choice {
+            off_color_count: 1,
+            ..high_pity
+        }));
+        assert!(Strategy::OffColor(Color::Red).is_chosen(&needed));
+        assert!(!Strategy::OffColor(Color::Red).is_chosen(&off_color));
+    }
+
+    #[test]
+    fn only_off_color_has_a_fallback() {
+        assert_eq!(Strategy::GoalColors.fallback_color(), None);
+        assert_eq!(
+            Strategy::OffColor(Color::Green).fallback_color(),
+            Some(Color::Green)
+        );
+    }
+}
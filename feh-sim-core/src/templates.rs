@@ -0,0 +1,120 @@
+use lazy_static::lazy_static;
+use strum::IntoEnumIterator;
+
+use crate::{Banner, BannerKind, Color};
+
+/// The saved banners, one line per banner. It is part of the build, so a
+/// malformed line is a bug rather than something to recover from.
+const TEMPLATE_DATA: &str = include_str!("../data/banner_templates.tsv");
+
+lazy_static! {
+    /// `TEMPLATE_DATA` once it has been parsed and checked, which only happens
+    /// the first time that it's needed.
+    static ref LIBRARY: Vec<BannerTemplate> = TEMPLATE_DATA
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(BannerTemplate::parse_line)
+        .collect();
+}
+
+/// A saved banner that can be loaded instead of entering its settings by hand.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BannerTemplate {
+    pub name: &'static str,
+    pub kind: BannerKind,
+    /// The color and name of each focus unit, in the order they are listed.
+    pub focus: Vec<(Color, &'static str)>,
+    /// The names of the focus units that are also 4* focus units.
+    pub fourstar_focus: Vec<&'static str>,
+}
+
+impl BannerTemplate {
+    /// Every saved banner, in the order they are listed.
+    pub fn library() -> &'static [BannerTemplate] {
+        &LIBRARY
+    }
+
+    /// Finds the saved banner with the given name, ignoring case.
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        BannerTemplate::library()
+            .iter()
+            .find(|template| template.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Creates the banner, with the usual settings for its kind. The 4* focus
+    /// units are moved to the front of their color, which is where the rest
+    /// of the banner settings expect them.
+    pub fn banner(&self) -> Banner {
+        let mut banner = Banner::default();
+        banner.set_kind(self.kind);
+        let mut focus_names: [Vec<String>; 4] = Default::default();
+        for four_star in [true, false] {
+            for &(color, name) in &self.focus {
+                if self.fourstar_focus.contains(&name) == four_star {
+                    focus_names[color as usize].push(name.to_string());
+                    if four_star {
+                        banner.fourstar_focus_sizes[color as usize] += 1;
+                    }
+                }
+            }
+        }
+        for (size, names) in banner.focus_sizes.iter_mut().zip(&focus_names) {
+            *size = names.len() as i8;
+        }
+        banner.focus_names = focus_names;
+        if banner.has_fourstar_focus() {
+            banner.starting_rates = Banner::fourstar_focus_rates();
+        }
+        banner
+    }
+
+    fn parse_line(line: &'static str) -> Self {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 4, "invalid banner template line \"{}\"", line);
+        let kind = BannerKind::iter()
+            .find(|kind| format!("{:?}", kind) == fields[1])
+            .expect("invalid banner kind");
+        let focus = fields[2]
+            .split(',')
+            .map(|unit| {
+                let (color, name) = unit.split_once(':').expect("invalid focus unit");
+                let color = Color::iter()
+                    .find(|c| c.to_string() == color)
+                    .expect("invalid focus unit color");
+                (color, name)
+            })
+            .collect::<Vec<_>>();
+        let fourstar_focus = match fields[3] {
+            "-" => vec![],
+            names => names.split(',').collect(),
+        };
+        for name in &fourstar_focus {
+            assert!(
+                focus.iter().any(|&(_, focus_name)| focus_name == *name),
+                "4* focus unit \"{}\" isn't one of the focus units",
+                name
+            );
+        }
+        let template = BannerTemplate {
+            name: fields[0],
+            kind,
+            focus,
+            fourstar_focus,
+        };
+        let rules = kind.rules();
+        let banner = template.banner();
+        assert!(
+            rules
+                .focus_sizes
+                .map_or(true, |sizes| sizes == banner.focus_sizes),
+            "wrong number of focus units for the kind of banner in \"{}\"",
+            line
+        );
+        assert!(
+            rules.fourstar_focus || !banner.has_fourstar_focus(),
+            "4* focus units on a kind of banner without them in \"{}\"",
+            line
+        );
+        template
+    }
+}
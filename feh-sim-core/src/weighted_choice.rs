@@ -41,48 +41,47 @@ impl Distribution<usize> for WeightedIndex4 {
     }
 }
 
-/// Optimized version of rand::WeightedIndex for a fixed-size collection of five floats.
+/// Optimized version of rand::WeightedIndex for a fixed-size collection of six floats.
 #[derive(Copy, Clone, Debug, Default)]
-pub struct WeightedIndex5 {
+pub struct WeightedIndex6 {
     // Cumulative weights stored for faster lookup
-    values: [f32; 5],
+    values: [f32; 6],
 }
 
-impl WeightedIndex5 {
+impl WeightedIndex6 {
     /// Constructs a sampler from the given weights. Weights do not need to sum to 1.
-    pub fn new<T: Into<f32> + Copy>(values: [T; 5]) -> Self {
-        let total = values[0].into()
-            + values[1].into()
-            + values[2].into()
-            + values[3].into()
-            + values[4].into();
-        Self {
-            values: [
-                values[0].into() / total,
-                (values[0].into() + values[1].into()) / total,
-                (values[0].into() + values[1].into() + values[2].into()) / total,
-                (values[0].into() + values[1].into() + values[2].into() + values[3].into()) / total,
-                1.0,
-            ],
+    pub fn new<T: Into<f32> + Copy>(values: [T; 6]) -> Self {
+        let mut cumulative = [0.0; 6];
+        let mut total = 0.0;
+        for (sum, &value) in cumulative.iter_mut().zip(&values) {
+            total += value.into();
+            *sum = total;
+        }
+        for sum in cumulative.iter_mut() {
+            *sum /= total;
         }
+        cumulative[5] = 1.0;
+        Self { values: cumulative }
     }
 }
 
-impl Distribution<usize> for WeightedIndex5 {
+impl Distribution<usize> for WeightedIndex6 {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
         let choice = rng.gen::<f32>();
-        if choice > self.values[1] {
-            if choice > self.values[2] {
-                if choice > self.values[3] {
-                    4
+        if choice > self.values[2] {
+            if choice > self.values[3] {
+                if choice > self.values[4] {
+                    5
                 } else {
-                    3
+                    4
                 }
             } else {
-                2
+                3
             }
         } else {
-            if choice > self.values[0] {
+            if choice > self.values[1] {
+                2
+            } else if choice > self.values[0] {
                 1
             } else {
                 0
@@ -1,76 +1,94 @@
 use seed::prelude::*;
 
-use crate::{Color, Msg};
+use strum::IntoEnumIterator;
 
-/// Representation of a summoning focus.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
-pub struct Banner {
-    pub focus_sizes: [i8; 4],
-    pub starting_rates: (u8, u8),
-    pub focus_charges: bool,
-    pub fourstar_focus: Option<Color>,
-}
-
-impl Default for Banner {
-    fn default() -> Self {
-        Banner {
-            focus_sizes: [1, 1, 1, 1],
-            starting_rates: (3, 3),
-            focus_charges: true,
-            fourstar_focus: None,
-        }
-    }
-}
+use feh_sim_core::{Banner, BannerKind, BannerTemplate, Color, Pool, PoolSizes};
 
-impl Banner {
-    /// Parses data from the representation used in query strings to share settings.
-    pub fn from_query_string(s: &str) -> Option<Self> {
-        let data = base64::decode(s).ok()?;
-        bincode::deserialize(&data).ok()
-    }
-}
+use crate::Msg;
 
 /// Section for choosing banner parameters.
-pub fn banner_selector(banner: &Banner) -> Node<Msg> {
-    let rate_option = |rates: (u8, u8), label: &str| -> Node<Msg> {
-        let mut attrs = attrs![
-            At::Value => format!("{} {}", rates.0, rates.1);
-        ];
-        if rates == banner.starting_rates {
+pub fn banner_selector(banner: &Banner, pool_sizes: PoolSizes) -> Node<Msg> {
+    let rules = banner.kind.rules();
+    let mut kind_select = select![
+        id!["banner_kind"],
+        input_ev("input", |text| {
+            if let Some(kind) = BannerKind::iter().find(|kind| format!("{:?}", kind) == text) {
+                Msg::BannerKindChange { kind }
+            } else {
+                Msg::Null
+            }
+        }),
+    ];
+    for kind in BannerKind::iter() {
+        let mut attrs = attrs![At::Value => format!("{:?}", kind)];
+        if kind == banner.kind {
             attrs.add(At::Selected, "");
         }
-        option![attrs, label]
-    };
+        kind_select.add_child(option![attrs, kind.to_string()]);
+    }
     div![
         id!["banner_selector"],
         div![
-            select![
-                id!["starting_rates"],
-                input_ev("input", |text| {
-                    if let &[Ok(first), Ok(second)] = &*text
-                        .split_whitespace()
-                        .map(str::parse::<u8>)
-                        .collect::<Vec<_>>()
-                    {
-                        Msg::BannerRateChange {
-                            rates: (first, second),
-                        }
-                    } else {
-                        Msg::Null
-                    }
-                }),
-                rate_option((3, 3), "3%/3% (Normal)"),
-                rate_option((5, 3), "5%/3% (Hero Fest)"),
-                rate_option((8, 0), "8%/0% (Legendary)"),
-                rate_option((4, 2), "4%/2% (Weekly Focus)"),
-                rate_option((6, 0), "6%/0% (Double Special Heroes)"),
-            ],
-            input![
-                id!["focus_charges_banner"],
-                simple_ev(Ev::Input, Msg::BannerFocusChargesToggle),
-                attrs![At::Type => "checkbox"; At::Checked => banner.focus_charges.as_at_value()],
-            ],
-            label![attrs![At::For => "focus_charges_banner"], "Focus charges?"]
+            template_selector(),
+            span![class!["padleft"], kind_select],
+            if rules.focus_charges {
+                nodes![
+                    input![
+                        id!["focus_charges_banner"],
+                        simple_ev(Ev::Input, Msg::BannerFocusChargesToggle),
+                        attrs![At::Type => "checkbox"; At::Checked => banner.focus_charges.as_at_value()],
+                    ],
+                    label![attrs![At::For => "focus_charges_banner"], "Focus charges?"],
+                ]
+            } else {
+                vec![]
+            },
+            if rules.spark {
+                nodes![
+                    input![
+                        id!["spark_banner"],
+                        simple_ev(Ev::Input, Msg::BannerSparkToggle),
+                        attrs![At::Type => "checkbox"; At::Checked => banner.spark_threshold.is_some().as_at_value()],
+                    ],
+                    label![attrs![At::For => "spark_banner"], "Spark?"],
+                ]
+            } else {
+                vec![]
+            },
+            if let Some(threshold) = banner.spark_threshold {
+                span![
+                    label![
+                        attrs![
+                            At::For => "spark_threshold";
+                        ],
+                        " Every ",
+                    ],
+                    input![
+                        id!["spark_threshold"],
+                        input_ev("input", |text| {
+                            if let Ok(threshold) = text.parse::<u32>() {
+                                Msg::BannerSparkThresholdChange { threshold }
+                            } else {
+                                Msg::BannerSparkThresholdChange { threshold: 0 }
+                            }
+                        }),
+                        attrs![
+                            At::Type => "number";
+                            At::Class => "small_number";
+                            At::Value => if threshold > 0 {
+                                threshold.to_string()
+                            } else {
+                                "".to_string()
+                            };
+                            At::Min => 1;
+                            At::Required => true;
+                        ],
+                    ],
+                    " summons",
+                ]
+            } else {
+                seed::empty()
+            },
         ],
         div![
             id!["focus_counts"],
@@ -206,73 +224,248 @@ pub fn banner_selector(banner: &Banner) -> Node<Msg> {
                     At::Required => true;
                 ],
             ],
-            if banner.starting_rates == (3, 3) {
-                nodes![
-                    label![
-                        attrs![
-                            At::For => "fourstar_focus";
-                        ],
-                        "4* focus:",
-                    ],
-                    select![
-                        id!["fourstar_focus"],
-                        input_ev("input", |text| {
-                            use Color::*;
-                            let focus = match &*text {
-                                "Red" => Some(Red),
-                                "Blue" => Some(Blue),
-                                "Green" => Some(Green),
-                                "Colorless" => Some(Colorless),
-                                _ => None,
-                            };
+            if rules.fourstar_focus {
+                fourstar_focus_counts(banner)
+            } else {
+                seed::empty()
+            },
+        ],
+        focus_names(banner),
+        rate_editor(banner),
+        pool_selector(pool_sizes),
+        pool_size_overrides(banner, pool_sizes),
+    ]
+}
 
-                            Msg::BannerFourstarFocusChange { focus }
-                        }),
-                        option![
-                            if banner.fourstar_focus == None {
-                                attrs![At::Selected => "1"]
-                            } else {
-                                attrs![]
-                            },
-                            "None"
-                        ],
-                        option![
-                            if banner.fourstar_focus == Some(Color::Red) {
-                                attrs![At::Selected => "1"]
-                            } else {
-                                attrs![]
-                            },
-                            "Red"
-                        ],
-                        option![
-                            if banner.fourstar_focus == Some(Color::Blue) {
-                                attrs![At::Selected => "1"]
-                            } else {
-                                attrs![]
-                            },
-                            "Blue"
-                        ],
-                        option![
-                            if banner.fourstar_focus == Some(Color::Green) {
-                                attrs![At::Selected => "1"]
-                            } else {
-                                attrs![]
-                            },
-                            "Green"
-                        ],
-                        option![
-                            if banner.fourstar_focus == Some(Color::Colorless) {
-                                attrs![At::Selected => "1"]
-                            } else {
-                                attrs![]
-                            },
-                            "Colorless"
-                        ],
-                    ],
-                ]
+/// Dropdown for loading one of the saved banners in place of the current one.
+fn template_selector() -> Node<Msg> {
+    let mut select = select![
+        id!["banner_template"],
+        input_ev("input", |text| {
+            let template = text
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| BannerTemplate::library().get(index));
+            if let Some(template) = template {
+                Msg::BannerSet {
+                    banner: template.banner(),
+                }
             } else {
-                vec![]
+                Msg::Null
+            }
+        }),
+        option![
+            attrs![At::Value => ""; At::Selected => ""],
+            "Load a saved banner",
+        ],
+    ];
+    for (index, template) in BannerTemplate::library().iter().enumerate() {
+        select.add_child(option![attrs![At::Value => index], template.name]);
+    }
+    select
+}
+
+/// Inputs for naming each of the focus units, so that goals can pick them by
+/// name.
+fn focus_names(banner: &Banner) -> Node<Msg> {
+    let mut names = div![id!["focus_names"], "Focus unit names:"];
+    for color in Color::iter() {
+        for slot in 0..banner.focus_sizes[color as usize].max(0) as usize {
+            let four_star = slot < banner.fourstar_focus_sizes[color as usize] as usize;
+            names.add_child(input![
+                class!["padleft"],
+                input_ev("input", move |name| Msg::BannerFocusNameChange {
+                    color,
+                    slot,
+                    name,
+                }),
+                attrs![
+                    At::Placeholder => format!("{}{}", color, if four_star { " 4*" } else { "" });
+                    At::Value => banner.focus_name(color, slot).unwrap_or("");
+                ],
+            ]);
+        }
+    }
+    names
+}
+
+/// Inputs for the number of 4* focus units of each color.
+fn fourstar_focus_counts(banner: &Banner) -> Node<Msg> {
+    let mut counts = span![id!["fourstar_focus_counts"], "4* focus:"];
+    for color in Color::iter() {
+        let id = format!("fourstar_focus_count_{}", color as usize);
+        counts.add_child(label![
+            attrs![
+                At::For => id.clone();
+            ],
+            format!(" {}:", color.to_string().chars().next().unwrap()),
+        ]);
+        counts.add_child(input![
+            id![id],
+            input_ev("input", move |text| Msg::BannerFourstarFocusSizeChange {
+                color,
+                quantity: text.parse::<u8>().unwrap_or(0),
+            }),
+            attrs![
+                At::Type => "number";
+                At::Class => "small_number";
+                At::Min => 0;
+                At::Value => banner.fourstar_focus_sizes[color as usize];
+            ],
+        ]);
+    }
+    counts
+}
+
+/// Inputs for the starting rate of each pool.
+fn rate_editor(banner: &Banner) -> Node<Msg> {
+    let pools = [
+        (Pool::Focus, "5* focus"),
+        (Pool::Fivestar, "5*"),
+        (Pool::FourstarFocus, "4* focus"),
+        (Pool::Fourstar, "4*"),
+        (Pool::Threestar, "3*"),
+        (Pool::FourstarSpecial, "Special 4*"),
+    ];
+    // The special 4* rate stays visible once it is set, so that it can be
+    // cleared after switching to a kind of banner that doesn't have one.
+    let show_special = banner.kind.rules().fourstar_special || banner.has_fourstar_special();
+    let mut inputs = div![id!["rate_editor"]];
+    for &(pool, label) in pools.iter() {
+        if pool == Pool::FourstarSpecial && !show_special {
+            continue;
+        }
+        let id = format!("rate_{}", pool as usize);
+        inputs.add_child(span![
+            label![
+                attrs![
+                    At::For => id.clone();
+                ],
+                format!("{}: ", label),
+            ],
+            input![
+                id![id],
+                input_ev("input", move |text| {
+                    if let Ok(rate) = text.parse::<f32>() {
+                        Msg::BannerRateEdit { pool, rate }
+                    } else if text.is_empty() {
+                        Msg::BannerRateEdit { pool, rate: 0.0 }
+                    } else {
+                        Msg::Null
+                    }
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Max => 100;
+                    At::Step => "any";
+                    At::Value => format_rate(banner.starting_rates[pool as usize]);
+                ],
+            ],
+            "% ",
+        ]);
+    }
+    let total: f32 = banner.starting_rates.iter().sum();
+    if !banner.has_valid_rates() {
+        inputs.add_child(p![if (total - 100.0).abs() >= 0.01 {
+            format!("The rates add up to {}%, not 100%.", format_rate(total))
+        } else {
+            "The 5* focus rate needs to be above 0%, the 4* focus rate needs to be above 0% \
+             only when there is a 4* focus unit, and the 4* and 3* rates can't both be 0%."
+                .to_string()
+        }]);
+    }
+    inputs
+}
+
+/// Writes a rate with at most two decimal places, leaving off trailing zeros.
+fn format_rate(rate: f32) -> String {
+    let text = format!("{:.2}", rate);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Dropdown for choosing which version of the summoning pools to use.
+fn pool_selector(pool_sizes: PoolSizes) -> Node<Msg> {
+    let mut select = select![
+        id!["pool_sizes"],
+        input_ev("input", |text| {
+            if let Some(pool_sizes) = PoolSizes::from_date(&text) {
+                Msg::PoolSizesChange { pool_sizes }
+            } else {
+                Msg::Null
             }
+        }),
+    ];
+    for sizes in PoolSizes::history().iter().rev() {
+        let mut attrs = attrs![At::Value => sizes.date];
+        if *sizes == pool_sizes {
+            attrs.add(At::Selected, "");
+        }
+        select.add_child(option![attrs, sizes.date]);
+    }
+    div![
+        label![
+            attrs![
+                At::For => "pool_sizes";
+            ],
+            "Pool as of ",
+        ],
+        select,
+    ]
+}
+
+/// Inputs for overriding the number of units of each color in the non-focus
+/// pools. Blank inputs keep the usual size, which is shown as a placeholder.
+fn pool_size_overrides(banner: &Banner, pool_sizes: PoolSizes) -> Node<Msg> {
+    let usual_sizes = [
+        pool_sizes.fivestar,
+        pool_sizes.fourstar,
+        pool_sizes.threestar,
+    ];
+    let mut table = table![
+        id!["pool_size_overrides"],
+        tr![th![], th!["R"], th!["B"], th!["G"], th!["C"]],
+    ];
+    for (rarity, &label) in ["5*", "4*", "3*"].iter().enumerate() {
+        let mut row = tr![th![label]];
+        for color in Color::iter() {
+            row.add_child(td![input![
+                input_ev("input", move |text| Msg::BannerPoolOverrideChange {
+                    rarity,
+                    color,
+                    size: text.parse::<u8>().ok(),
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Placeholder => usual_sizes[rarity][color as usize];
+                    At::Value => match banner.pool_overrides[rarity][color as usize] {
+                        Some(size) => size.to_string(),
+                        None => "".to_string(),
+                    };
+                ],
+            ]]);
+        }
+        table.add_child(row);
+    }
+    div![
+        details![
+            // Banners that leave units out of their pools need them entered,
+            // so start with the inputs showing.
+            if banner.kind.rules().restricted_pools {
+                attrs!["open" => ""]
+            } else {
+                attrs![]
+            },
+            summary!["Pool sizes"],
+            table,
         ],
+        if banner.has_valid_pools(pool_sizes) {
+            seed::empty()
+        } else {
+            p!["Every pool needs at least one unit in it."]
+        },
     ]
 }
@@ -0,0 +1,222 @@
+use seed::prelude::*;
+
+use feh_sim_core::goal::Goal;
+use feh_sim_core::sim::campaign::{CampaignResults, Plan};
+use feh_sim_core::{stats, Banner, Progress};
+
+use crate::Msg;
+
+/// One banner of a campaign, saved from the settings that were current when
+/// it was added.
+#[derive(Clone, Debug)]
+pub struct CampaignEntry {
+    pub banner: Banner,
+    pub goal: Goal,
+    pub progress: Progress,
+    /// Orbs gained between the previous banner and this one.
+    pub income: u32,
+    /// The most orbs to spend on this banner, if any.
+    pub limit: Option<u32>,
+    /// How much reaching the goal is worth when looking for the best limits.
+    pub weight: f64,
+}
+
+/// Section for planning several banners in a row that share one orb budget.
+pub fn campaign_planner(
+    entries: &[CampaignEntry],
+    orbs: u32,
+    results: &CampaignResults,
+    plans: &[Plan],
+    can_add: bool,
+) -> Node<Msg> {
+    let mut list = ol![];
+    for (index, entry) in entries.iter().enumerate() {
+        let id = format!("campaign_income_{}", index);
+        let limit_id = format!("campaign_limit_{}", index);
+        let weight_id = format!("campaign_weight_{}", index);
+        list.add_child(li![
+            entry.goal.as_custom(&entry.banner).describe(&entry.banner),
+            if index > 0 {
+                span![
+                    label![
+                        class!["padleft"],
+                        attrs![
+                            At::For => id.clone();
+                        ],
+                        "Orbs gained before it: ",
+                    ],
+                    input![
+                        id![id],
+                        input_ev(Ev::Input, move |text| Msg::CampaignIncomeChange {
+                            index,
+                            income: text.parse::<u32>().unwrap_or(0),
+                        }),
+                        attrs![
+                            At::Type => "number";
+                            At::Class => "small_number";
+                            At::Min => 0;
+                            At::Value => match entry.income {
+                                0 => "".to_string(),
+                                income => income.to_string(),
+                            };
+                        ],
+                    ],
+                ]
+            } else {
+                seed::empty()
+            },
+            label![
+                class!["padleft"],
+                attrs![
+                    At::For => limit_id.clone();
+                ],
+                "Stop after: ",
+            ],
+            input![
+                id![limit_id],
+                input_ev(Ev::Input, move |text| Msg::CampaignLimitChange {
+                    index,
+                    limit: text.parse::<u32>().ok(),
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Value => entry.limit.map_or("".to_string(), |limit| limit.to_string());
+                ],
+            ],
+            " orbs",
+            label![
+                class!["padleft"],
+                attrs![
+                    At::For => weight_id.clone();
+                ],
+                "Worth: ",
+            ],
+            input![
+                id![weight_id],
+                input_ev(Ev::Input, move |text| {
+                    match text.parse::<f64>() {
+                        Ok(weight) if weight >= 0.0 && weight.is_finite() => {
+                            Msg::CampaignWeightChange { index, weight }
+                        }
+                        _ => Msg::Null,
+                    }
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Step => "any";
+                    At::Value => entry.weight;
+                ],
+            ],
+            if results.samples > 0 {
+                span![
+                    class!["padleft"],
+                    format!("{:.1}% chance", results.success(index) * 100.0),
+                ]
+            } else {
+                seed::empty()
+            },
+            button![
+                class!["padleft"],
+                simple_ev(Ev::Click, Msg::CampaignRemove { index }),
+                "Remove",
+            ],
+        ]);
+    }
+
+    div![
+        id!["campaign_planner"],
+        div![
+            "Campaign: ",
+            button![
+                simple_ev(Ev::Click, Msg::CampaignAdd),
+                if !can_add {
+                    attrs![At::Disabled => true]
+                } else {
+                    attrs![]
+                },
+                "Add this banner and goal",
+            ],
+            label![
+                class!["padleft"],
+                attrs![
+                    At::For => "campaign_orbs";
+                ],
+                "Starting orbs: ",
+            ],
+            input![
+                id!["campaign_orbs"],
+                input_ev(Ev::Input, |text| Msg::CampaignOrbsChange {
+                    orbs: text.parse::<u32>().unwrap_or(0),
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Value => orbs;
+                ],
+            ],
+            button![
+                class!["padleft"],
+                simple_ev(Ev::Click, Msg::CampaignRun),
+                if entries.is_empty() {
+                    attrs![At::Disabled => true]
+                } else {
+                    attrs![]
+                },
+                "Run campaign",
+            ],
+            button![
+                class!["padleft"],
+                simple_ev(Ev::Click, Msg::CampaignOptimize),
+                if entries.len() < 2 {
+                    attrs![At::Disabled => true]
+                } else {
+                    attrs![]
+                },
+                "Find best limits",
+            ],
+        ],
+        list,
+        if results.samples > 0 {
+            p![format!(
+                "{:.1}% chance of reaching every goal, with {:.0} orbs left on average ({} samples)",
+                results.joint_success() * 100.0,
+                stats::mean(&results.orbs_left),
+                results.samples,
+            )]
+        } else {
+            seed::empty()
+        },
+        plan_table(plans),
+    ]
+}
+
+/// Table of the best stop-loss limits found for the campaign, with the chance
+/// of reaching each goal under them.
+fn plan_table(plans: &[Plan]) -> Node<Msg> {
+    if plans.is_empty() {
+        return seed::empty();
+    }
+    let mut header = tr![th!["Score"], th!["All goals"]];
+    for index in 1..=plans[0].limits.len() {
+        header.add_child(th![format!("Stop after #{}", index)]);
+        header.add_child(th![format!("Goal #{}", index)]);
+    }
+    let mut table = table![id!["campaign_plans"], header];
+    for plan in plans {
+        let mut row = tr![
+            td![format!("{:.2}", plan.score)],
+            td![format!("{:.1}%", plan.results.joint_success() * 100.0)],
+        ];
+        for (index, limit) in plan.limits.iter().enumerate() {
+            row.add_child(td![limit.map_or("-".to_string(), |limit| limit.to_string())]);
+            row.add_child(td![format!("{:.1}%", plan.results.success(index) * 100.0)]);
+        }
+        table.add_child(row);
+    }
+    table
+}
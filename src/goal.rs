@@ -1,245 +1,16 @@
 use seed::prelude::*;
 
 use std::convert::TryFrom;
-use std::fmt;
 
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
 
-use serde::{Deserialize, Serialize};
+use feh_sim_core::goal::{
+    copies_for_merge_level, merge_level, Goal, GoalKind, GoalNode, GoalPart, GoalPreset,
+    PartTarget, MAX_MERGE_LEVEL,
+};
+use feh_sim_core::{Banner, Color};
 
-use crate::{banner::Banner, Color, Msg};
-
-/// Pre-set options for common goals.
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq, Eq, Serialize, Deserialize)]
-pub enum GoalPreset {
-    AnyFocus,
-    AllFocus,
-    RedFocus,
-    AnyRed,
-    RedFourstarFocus,
-    BlueFocus,
-    AnyBlue,
-    BlueFourstarFocus,
-    GreenFocus,
-    AnyGreen,
-    GreenFourstarFocus,
-    ColorlessFocus,
-    AnyColorless,
-    ColorlessFourstarFocus,
-}
-
-impl fmt::Display for GoalPreset {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use crate::goal::GoalPreset::*;
-        let s = match *self {
-            AnyFocus => "Any 5* focus unit",
-            AllFocus => "All focus units",
-            RedFocus => "Specific red 5* focus unit",
-            RedFourstarFocus => "The red 4* focus unit",
-            AnyRed => "Any red 5* focus unit",
-            BlueFocus => "Specific blue 5* focus unit",
-            BlueFourstarFocus => "The blue 4* focus unit",
-            AnyBlue => "Any blue 5* focus unit",
-            GreenFocus => "Specific green 5* focus unit",
-            GreenFourstarFocus => "The green 4* focus unit",
-            AnyGreen => "Any green 5* focus unit",
-            ColorlessFocus => "Specific colorless 5* focus unit",
-            AnyColorless => "Any colorless 5* focus unit",
-            ColorlessFourstarFocus => "The colorless 4* focus unit",
-        };
-        f.write_str(s)
-    }
-}
-
-impl TryFrom<u8> for GoalPreset {
-    type Error = ();
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        for variant in GoalPreset::iter() {
-            if variant as usize == value as usize {
-                return Ok(variant);
-            }
-        }
-        Err(())
-    }
-}
-
-impl GoalPreset {
-    /// Determines whether or not the selected preset is a goal that it is
-    /// possible to achieve on the banner.
-    pub fn is_available(self, banner: &Banner) -> bool {
-        use GoalPreset::*;
-        match self {
-            AnyFocus | AllFocus => banner.focus_sizes.iter().any(|&x| x > 0),
-            RedFocus | AnyRed => banner.focus_sizes[0] > 0,
-            BlueFocus | AnyBlue => banner.focus_sizes[1] > 0,
-            GreenFocus | AnyGreen => banner.focus_sizes[2] > 0,
-            ColorlessFocus | AnyColorless => banner.focus_sizes[3] > 0,
-            RedFourstarFocus => {
-                banner.fourstar_focus == Some(Color::Red) && banner.focus_sizes[0] > 0
-            }
-            BlueFourstarFocus => {
-                banner.fourstar_focus == Some(Color::Blue) && banner.focus_sizes[1] > 0
-            }
-            GreenFourstarFocus => {
-                banner.fourstar_focus == Some(Color::Green) && banner.focus_sizes[2] > 0
-            }
-            ColorlessFourstarFocus => {
-                banner.fourstar_focus == Some(Color::Colorless) && banner.focus_sizes[3] > 0
-            }
-        }
-    }
-
-    /// Says whether or not the preset has only a single unit that counts for
-    /// completing the goal.
-    fn is_single_target(&self) -> bool {
-        use GoalPreset::*;
-        match self {
-            RedFocus
-            | BlueFocus
-            | GreenFocus
-            | ColorlessFocus
-            | RedFourstarFocus
-            | BlueFourstarFocus
-            | GreenFourstarFocus
-            | ColorlessFourstarFocus => true,
-            _ => false,
-        }
-    }
-}
-
-/// Whether the given goal is to achieve all of the goal parts or just a single one.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub enum GoalKind {
-    Any,
-    All,
-}
-
-/// A single unit that the goal is trying to obtain.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-pub struct GoalPart {
-    pub unit_color: Color,
-    pub num_copies: u8,
-    pub four_star: bool,
-}
-
-/// The flexible representation of a goal
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct CustomGoal {
-    pub kind: GoalKind,
-    pub goals: Vec<GoalPart>,
-}
-
-/// The goal of a summoning session.
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum Goal {
-    Custom(CustomGoal),
-    Preset(GoalPreset, u8),
-}
-
-impl Default for Goal {
-    fn default() -> Self {
-        Goal::Preset(GoalPreset::AnyFocus, 1)
-    }
-}
-
-impl Goal {
-    /// Convert the current preset into a custom goal or retreive the current
-    /// custom goal.
-    pub fn as_custom(&self, banner: &Banner) -> CustomGoal {
-        use crate::goal::GoalKind::*;
-        use crate::goal::GoalPreset::*;
-        use crate::Color::*;
-
-        let (preset, count) = match self {
-            Goal::Preset(preset, count) => (*preset, *count),
-            Goal::Custom(custom) => return custom.clone(),
-        };
-
-        let count = if preset.is_single_target() {
-            count.max(1)
-        } else {
-            1
-        };
-
-        let kind = match preset {
-            AllFocus => All,
-            // Every other preset is either Any* or has only one target
-            _ => Any,
-        };
-        let mut custom_goal = CustomGoal {
-            kind,
-            goals: vec![],
-        };
-
-        let mut add_color_goal = |color: Color, four_star: bool| {
-            custom_goal.goals.push(GoalPart {
-                unit_color: color,
-                num_copies: count,
-                four_star,
-            });
-        };
-        // Add an individual GoalPart for each focus unit that matches the
-        // conditions of the overall goal.
-        match preset {
-            AllFocus | AnyFocus => {
-                for idx in 0..banner.focus_sizes.len() {
-                    for _ in 0..banner.focus_sizes[idx] {
-                        add_color_goal(Color::try_from(idx as u8).unwrap(), false);
-                    }
-                }
-            }
-            RedFocus => add_color_goal(Red, false),
-            BlueFocus => add_color_goal(Blue, false),
-            GreenFocus => add_color_goal(Green, false),
-            ColorlessFocus => add_color_goal(Colorless, false),
-            AnyRed => {
-                for _ in 0..banner.focus_sizes[0] {
-                    add_color_goal(Red, false)
-                }
-            }
-            AnyBlue => {
-                for _ in 0..banner.focus_sizes[1] {
-                    add_color_goal(Blue, false)
-                }
-            }
-            AnyGreen => {
-                for _ in 0..banner.focus_sizes[2] {
-                    add_color_goal(Green, false)
-                }
-            }
-            AnyColorless => {
-                for _ in 0..banner.focus_sizes[3] {
-                    add_color_goal(Colorless, false)
-                }
-            }
-            RedFourstarFocus => add_color_goal(Red, true),
-            BlueFourstarFocus => add_color_goal(Blue, true),
-            GreenFourstarFocus => add_color_goal(Green, true),
-            ColorlessFourstarFocus => add_color_goal(Colorless, true),
-        }
-
-        custom_goal
-    }
-
-    /// Checks whether or not the goal is possible on the given banner.
-    pub fn is_available(&self, banner: &Banner) -> bool {
-        match self {
-            Goal::Custom(custom_goal) => custom_goal
-                .goals
-                .iter()
-                .any(|&GoalPart { unit_color, .. }| banner.focus_sizes[unit_color as usize] > 0),
-            Goal::Preset(preset, _) => preset.is_available(banner),
-        }
-    }
-
-    /// Parses data from the representation used in query strings to share settings.
-    pub fn from_query_string(s: &str) -> Option<Self> {
-        let data = base64::decode(s).ok()?;
-        bincode::deserialize(&data).ok()
-    }
-}
+use crate::Msg;
 
 /// Section for selecting the goal.
 pub fn goal_selector(goal: &Goal, banner: &Banner) -> Node<Msg> {
@@ -288,119 +59,154 @@ pub fn goal_selector(goal: &Goal, banner: &Banner) -> Node<Msg> {
     div![
         id!["goal_selector"],
         select,
-        if let Goal::Preset(preset, count) = goal {
-            if preset.is_single_target() {
-                span![
-                    label![
-                        attrs![
-                            At::For => "goal_count";
-                        ],
-                        "Count: ",
+        if let Goal::Preset(_, count) = goal {
+            span![
+                class!["padleft"],
+                label![
+                    attrs![
+                        At::For => "goal_count";
                     ],
-                    input![
-                        id!["goal_count"],
-                        input_ev("input", |text| {
-                            if let Ok(quantity) = text.parse::<u8>() {
-                                Msg::GoalPresetQuantityChange { quantity }
-                            } else {
-                                Msg::GoalPresetQuantityChange { quantity: 0 }
-                            }
-                        }),
-                        attrs! [
-                            At::Type => "number";
-                            At::Value => if *count > 0 {
-                                count.to_string()
-                            } else {
-                                "".to_string()
-                            };
-                            At::Class => "small_number";
-                            At::Min => 1;
-                            At::Required => true;
-                        ],
-                    ]
-                ]
-            } else {
-                seed::empty()
-            }
+                    "Merge level: ",
+                ],
+                select![
+                    id!["goal_count"],
+                    input_ev("input", |text| {
+                        if let Ok(quantity) = text.parse::<u8>() {
+                            Msg::GoalPresetQuantityChange { quantity }
+                        } else {
+                            Msg::Null
+                        }
+                    }),
+                    merge_level_options(*count, 1),
+                ],
+            ]
         } else {
             seed::empty()
         },
-        advanced_goal_selector(goal),
+        advanced_goal_selector(goal, banner),
     ]
 }
 
 /// Subsection for selecting the goal using the detailed representation instead of
 /// a preset.
-fn advanced_goal_selector(goal: &Goal) -> Node<Msg> {
+fn advanced_goal_selector(goal: &Goal, banner: &Banner) -> Node<Msg> {
     if let Goal::Custom(custom_goal) = goal {
-        let mut base = div![style!["margin-left" => "2em";]];
-        if custom_goal.goals.len() > 1 {
-            base.add_child(select![
-                input_ev(Ev::Input, |text| match &*text {
-                    "Any" => Msg::GoalKindChange {
-                        kind: GoalKind::Any
-                    },
-                    "All" => Msg::GoalKindChange {
-                        kind: GoalKind::All
-                    },
-                    _ => Msg::Null,
-                }),
-                option![
-                    attrs![
-                        At::Value => "Any";
-                    ],
-                    if custom_goal.kind == GoalKind::Any {
-                        attrs![At::Selected => ""]
-                    } else {
-                        attrs![]
-                    },
-                    "Any of these",
+        let mut base = group_editor(custom_goal.kind, &custom_goal.goals, banner, &mut 0, &mut 0);
+
+        if banner.has_fourstar_special() || custom_goal.fourstar_merges {
+            base.add_child(div![
+                input![
+                    id!["fourstar_merges"],
+                    simple_ev(Ev::Input, Msg::GoalFourstarMergesToggle),
+                    attrs![At::Type => "checkbox"; At::Checked => custom_goal.fourstar_merges.as_at_value()],
                 ],
-                option![
-                    attrs![
-                        At::Value => "All";
-                    ],
-                    if custom_goal.kind == GoalKind::All {
-                        attrs![At::Selected => ""]
-                    } else {
-                        attrs![]
-                    },
-                    "All of these",
+                label![
+                    attrs![At::For => "fourstar_merges"],
+                    "Count 4* copies from the special 4* rate as merges",
                 ],
             ]);
         }
 
-        for (index, goal_part) in custom_goal.goals.iter().enumerate() {
-            let mut color_select = select![input_ev(Ev::Input, move |value| {
-                if let Some(color) = value
-                    .parse::<u8>()
-                    .ok()
-                    .and_then(|num| Color::try_from(num).ok())
-                {
-                    Msg::GoalPartColorChange { index, color }
-                } else {
-                    Msg::Null
-                }
-            }),];
-            for color in Color::iter() {
-                let mut attrs = attrs![At::Value => color as usize];
-                if goal_part.unit_color == color {
-                    attrs.add(At::Selected, "");
-                }
-                color_select.add_child(option![attrs, color.to_string()]);
+        base
+    } else {
+        seed::empty()
+    }
+}
+
+/// Editor for one group of a custom goal and everything nested in it.
+/// `group` and `part` are the numbers of groups and parts that come before it,
+/// which are the numbers that the goal uses to refer to them.
+fn group_editor(
+    kind: GoalKind,
+    nodes: &[GoalNode],
+    banner: &Banner,
+    group: &mut usize,
+    part: &mut usize,
+) -> Node<Msg> {
+    let this_group = *group;
+    *group += 1;
+    let mut base = div![style!["margin-left" => "2em";]];
+    if nodes.len() > 1 {
+        base.add_child(kind_selector(this_group, kind, nodes.len()));
+    }
+
+    for node in nodes {
+        match node {
+            GoalNode::Part(goal_part) => {
+                base.add_child(part_editor(*part, goal_part, banner));
+                *part += 1;
             }
-            base.add_child(div![
-                button![
-                    simple_ev(
-                        Ev::Click,
-                        Msg::GoalPartQuantityChange { index, quantity: 0 }
-                    ),
-                    "X",
-                ],
+            GoalNode::Group(nested) => {
+                base.add_child(group_editor(
+                    nested.kind,
+                    &nested.goals,
+                    banner,
+                    group,
+                    part,
+                ));
+            }
+        }
+    }
+
+    base.add_child(button![
+        simple_ev(
+            Ev::Click,
+            Msg::GoalPartAdd {
+                group: this_group,
+                color: Color::Red,
+                quantity: 1
+            }
+        ),
+        "+",
+    ]);
+    base.add_child(button![
+        simple_ev(Ev::Click, Msg::GoalGroupAdd { group: this_group }),
+        "+ Group",
+    ]);
+    base
+}
+
+/// Dropdown for how many of the `parts` entries in the `group`th group need to
+/// happen.
+fn kind_selector(group: usize, kind: GoalKind, parts: usize) -> Node<Msg> {
+    let option = |value: &str, label: &str, selected: bool| {
+        option![
+            attrs![
+                At::Value => value;
+            ],
+            if selected {
+                attrs![At::Selected => ""]
+            } else {
+                attrs![]
+            },
+            label,
+        ]
+    };
+    span![
+        select![
+            input_ev(Ev::Input, move |text| {
+                let kind = match &*text {
+                    "Any" => GoalKind::Any,
+                    "All" => GoalKind::All,
+                    "AtLeast" => GoalKind::AtLeast(2.min(parts as u8)),
+                    _ => return Msg::Null,
+                };
+                Msg::GoalKindChange { group, kind }
+            }),
+            option("Any", "Any of these", kind == GoalKind::Any),
+            option("All", "All of these", kind == GoalKind::All),
+            option("AtLeast", "At least", matches!(kind, GoalKind::AtLeast(_))),
+        ],
+        if let GoalKind::AtLeast(count) = kind {
+            span![
+                " ",
                 input![
-                    input_ev(Ev::Input, move |value| {
-                        if let Ok(quantity) = value.parse::<u8>() {
-                            Msg::GoalPartQuantityChange { index, quantity }
+                    input_ev(Ev::Input, move |text| {
+                        if let Ok(count) = text.parse::<u8>() {
+                            Msg::GoalKindChange {
+                                group,
+                                kind: GoalKind::AtLeast(count),
+                            }
                         } else {
                             Msg::Null
                         }
@@ -408,30 +214,159 @@ fn advanced_goal_selector(goal: &Goal) -> Node<Msg> {
                     attrs![
                         At::Type => "number";
                         At::Class => "small_number";
-                        At::Min => 0;
+                        At::Min => 1;
+                        At::Max => parts;
                         At::Required => true;
-                        At::Value => goal_part.num_copies;
-                    ]
+                        At::Value => count;
+                    ],
                 ],
-                " copies of a specific ",
-                color_select,
-                " unit",
-            ]);
-        }
+                " of these",
+            ]
+        } else {
+            seed::empty()
+        },
+    ]
+}
 
-        base.add_child(button![
-            simple_ev(
-                Ev::Click,
-                Msg::GoalPartAdd {
-                    color: Color::Red,
-                    quantity: 1
-                }
-            ),
-            "+",
-        ]);
+/// Options for choosing a number of copies by the merge level that they reach,
+/// starting from `min_copies`. A count of copies beyond the highest merge
+/// level gets an option of its own, so that it isn't lost.
+pub fn merge_level_options(copies: u8, min_copies: u8) -> Vec<Node<Msg>> {
+    let max_copies = copies_for_merge_level(MAX_MERGE_LEVEL);
+    let mut counts = (min_copies..=max_copies).collect::<Vec<_>>();
+    if copies > max_copies {
+        counts.push(copies);
+    }
+    counts
+        .into_iter()
+        .map(|count| {
+            let mut attrs = attrs![At::Value => count];
+            if count == copies {
+                attrs.add(At::Selected, "");
+            }
+            let label = match merge_level(count) {
+                None => "None".to_string(),
+                Some(level) if level <= MAX_MERGE_LEVEL => format!("+{}", level),
+                Some(_) => format!("{} copies", count),
+            };
+            option![attrs, label]
+        })
+        .collect()
+}
 
-        base
-    } else {
-        seed::empty()
+/// Inputs for a single unit target, which is the `index`th part of the goal.
+fn part_editor(index: usize, goal_part: &GoalPart, banner: &Banner) -> Node<Msg> {
+    let mut color_select = select![input_ev(Ev::Input, move |value| {
+        if let Some(color) = value
+            .parse::<u8>()
+            .ok()
+            .and_then(|num| Color::try_from(num).ok())
+        {
+            Msg::GoalPartColorChange { index, color }
+        } else {
+            Msg::Null
+        }
+    }),];
+    for color in Color::iter() {
+        let mut attrs = attrs![At::Value => color as usize];
+        if goal_part.unit_color == color {
+            attrs.add(At::Selected, "");
+        }
+        color_select.add_child(option![attrs, color.to_string()]);
+    }
+    let has_fourstar_focus = banner.fourstar_focus_sizes[goal_part.unit_color as usize] > 0;
+    let mut target_select = select![input_ev(Ev::Input, move |value| {
+        let target = match &*value {
+            "Focus" => PartTarget::Focus,
+            "FourstarFocus" => PartTarget::FourstarFocus,
+            "NonFocus" => PartTarget::NonFocus,
+            "AnyFivestar" => PartTarget::AnyFivestar,
+            _ => return Msg::Null,
+        };
+        Msg::GoalPartTargetChange { index, target }
+    }),];
+    for &(target, value, label) in &[
+        (PartTarget::Focus, "Focus", "5* focus"),
+        (PartTarget::FourstarFocus, "FourstarFocus", "4* focus"),
+        (PartTarget::NonFocus, "NonFocus", "non-focus 5*"),
+        (PartTarget::AnyFivestar, "AnyFivestar", "any 5*"),
+    ] {
+        let mut attrs = attrs![At::Value => value];
+        if goal_part.target == target {
+            attrs.add(At::Selected, "");
+        } else if target == PartTarget::FourstarFocus && !has_fourstar_focus {
+            attrs.add(At::Disabled, "");
+        }
+        target_select.add_child(option![attrs, label]);
+    }
+    // Focus units can be picked by name once the banner has names for them.
+    let mut unit_select = select![input_ev(Ev::Input, move |value| {
+        let unit = value.split_once(':').and_then(|(color, slot)| {
+            let color = Color::try_from(color.parse::<u8>().ok()?).ok()?;
+            Some((color, slot.parse::<u8>().ok()?))
+        });
+        Msg::GoalPartUnitChange { index, unit }
+    }),];
+    unit_select.add_child(option![attrs![At::Value => ""], "unnamed"]);
+    let mut has_names = false;
+    for color in Color::iter() {
+        for slot in 0..banner.focus_sizes[color as usize].max(0) as u8 {
+            if let Some(name) = banner.focus_name(color, slot as usize) {
+                let mut attrs = attrs![At::Value => format!("{}:{}", color as u8, slot)];
+                if goal_part.unit_color == color && goal_part.unit == Some(slot) {
+                    attrs.add(At::Selected, "");
+                }
+                unit_select.add_child(option![attrs, name]);
+                has_names = true;
+            }
+        }
     }
+    let quantity_changed = move |value: String| {
+        if let Ok(quantity) = value.parse::<u8>() {
+            Msg::GoalPartQuantityChange { index, quantity }
+        } else {
+            Msg::Null
+        }
+    };
+    div![
+        button![simple_ev(Ev::Click, Msg::GoalPartRemove { index }), "X"],
+        if goal_part.target == PartTarget::AnyFivestar {
+            vec![
+                input![
+                    input_ev(Ev::Input, quantity_changed),
+                    attrs![
+                        At::Type => "number";
+                        At::Class => "small_number";
+                        At::Min => 1;
+                        At::Required => true;
+                        At::Value => goal_part.num_copies;
+                    ]
+                ],
+                span![" of "],
+                target_select,
+                span![" units, focus or not"],
+            ]
+        } else {
+            vec![
+                span![" A specific "],
+                color_select,
+                span![" "],
+                target_select,
+                span![" unit"],
+                if has_names
+                    && (goal_part.target == PartTarget::Focus
+                        || goal_part.target == PartTarget::FourstarFocus)
+                {
+                    span![" (", unit_select, ")"]
+                } else {
+                    seed::empty()
+                },
+                span![" at "],
+                select![
+                    input_ev(Ev::Input, quantity_changed),
+                    merge_level_options(goal_part.num_copies, 1),
+                ],
+            ]
+        },
+    ]
 }
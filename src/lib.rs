@@ -2,30 +2,31 @@
 extern crate seed;
 use seed::prelude::*;
 
-use std::convert::TryFrom;
-use std::fmt;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 
-use strum_macros::EnumIter;
-
-use serde::{Deserialize, Serialize};
+use feh_sim_core::counter::Counter;
+use feh_sim_core::goal::{Goal, GoalGroup, GoalKind, GoalNode, GoalPart, GoalPreset, PartTarget};
+use feh_sim_core::sim::campaign::{optimize_limits, Campaign, CampaignResults, Plan};
+use feh_sim_core::sim::{exact, Sim, CHUNK_SIZE};
+use feh_sim_core::strategy::Strategy;
+use feh_sim_core::{
+    legacy, Banner, BannerKind, Color, IncomeProfile, Pool, PoolSizes, Progress, PERMALINK_VERSION,
+};
 
 mod banner;
-use banner::Banner;
 
 mod goal;
-use goal::{Goal, GoalKind, GoalPart, GoalPreset};
-
-mod results;
 
-mod sim;
-use sim::Sim;
+mod progress;
 
-mod weighted_choice;
+mod strategy;
+use strategy::Comparison;
 
-mod stats;
+mod campaign;
+use campaign::CampaignEntry;
 
-mod counter;
-use counter::Counter;
+mod results;
 
 mod subpages;
 
@@ -33,62 +34,6 @@ mod query_string;
 
 // Model
 
-#[repr(u8)]
-#[derive(Copy, Clone, PartialEq, Eq, Debug, EnumIter, Serialize, Deserialize)]
-pub enum Color {
-    Red,
-    Blue,
-    Green,
-    Colorless,
-}
-
-impl fmt::Display for Color {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-impl TryFrom<u8> for Color {
-    type Error = ();
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        use Color::*;
-        Ok(match value {
-            0 => Red,
-            1 => Blue,
-            2 => Green,
-            3 => Colorless,
-            _ => return Err(()),
-        })
-    }
-}
-
-#[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum Pool {
-    Focus,
-    Fivestar,
-    FourstarFocus,
-    Fourstar,
-    Threestar,
-}
-
-impl TryFrom<u8> for Pool {
-    type Error = ();
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        use Pool::*;
-        Ok(match value {
-            0 => Focus,
-            1 => Fivestar,
-            2 => FourstarFocus,
-            3 => Fourstar,
-            4 => Threestar,
-            _ => return Err(()),
-        })
-    }
-}
-
 /// The current page that the application is on.
 #[derive(Copy, Clone, Debug)]
 pub enum Page {
@@ -103,21 +48,118 @@ impl Default for Page {
     }
 }
 
+/// The method used to find the results.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// Run random simulations, which gets more precise as it runs longer.
+    Simulation,
+    /// Calculate the probabilities exactly, which can take a while for
+    /// complicated goals.
+    Exact,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::Simulation
+    }
+}
+
 /// Data model for the app.
 #[derive(Default, Debug)]
 struct Model {
     /// The data that the simulation has gathered so far.
     pub data: Counter,
+    /// The number of goal copies that each simulation had gotten when it went
+    /// over the orb budget.
+    pub budget_copies: Counter,
+    /// The number of orbs available to spend, if given.
+    pub budget: Option<u32>,
+    /// Where orbs come from over time, if the days of saving needed should be
+    /// shown.
+    pub income: Option<IncomeProfile>,
+    /// The exact distribution, if it has been calculated.
+    pub exact_data: Counter<f64>,
+    /// The method used to find the results.
+    pub engine: Engine,
+    /// The seed for the simulations, so that a run can be reproduced.
+    pub seed: u64,
     /// The parameters of the current banner.
     pub banner: Banner,
+    /// The sizes of the non-focus summoning pools.
+    pub pool_sizes: PoolSizes,
     /// The paremeters of the current goal.
     pub goal: Goal,
+    /// How far into the banner the summoner already is.
+    pub progress: Progress,
+    /// The strategy for choosing which stones to pull.
+    pub strategy: Strategy,
+    /// Results of the different strategies, if they have been compared.
+    pub comparison: Vec<Comparison>,
+    /// The banners of the campaign being planned, in order.
+    pub campaign: Vec<CampaignEntry>,
+    /// The number of orbs available at the start of the campaign.
+    pub campaign_orbs: u32,
+    /// The results of simulating the campaign, if it has been run.
+    pub campaign_results: CampaignResults,
+    /// The best stop-loss limits found for the campaign, if they have been
+    /// searched for.
+    pub campaign_plans: Vec<Plan>,
     /// The current page that the application is on.
     pub curr_page: Page,
     /// The point on the graph that the user has chose to highlight.
     pub graph_highlight: Option<f32>,
 }
 
+impl Model {
+    /// Discards the results, which are no longer valid after the parameters
+    /// have changed.
+    fn clear_data(&mut self) {
+        self.data.clear();
+        self.budget_copies.clear();
+        self.exact_data.clear();
+        self.comparison.clear();
+    }
+
+    /// Discards the results of the campaign, which are no longer valid after
+    /// its settings have changed.
+    fn clear_campaign_results(&mut self) {
+        self.campaign_results = CampaignResults::default();
+        self.campaign_plans.clear();
+    }
+
+    /// Checks whether the current settings can be simulated.
+    fn can_run(&self) -> bool {
+        self.goal.is_available(&self.banner, self.pool_sizes)
+            && self.banner.follows_kind_rules()
+            && self.banner.has_valid_rates()
+            && self.banner.has_valid_pools(self.pool_sizes)
+    }
+
+    /// Creates a simulator with the current settings.
+    fn sim(&self) -> Sim {
+        let mut sim = Sim::new(self.banner.clone(), self.goal.clone(), self.seed);
+        sim.set_pool_sizes(self.pool_sizes);
+        sim.set_budget(self.budget);
+        sim.set_progress(self.progress.clone());
+        sim.set_strategy(self.strategy);
+        sim
+    }
+
+    /// Creates the campaign with the current pool sizes and strategy.
+    fn campaign(&self) -> Campaign {
+        let mut campaign = Campaign::new(self.campaign_orbs);
+        for (index, entry) in self.campaign.iter().enumerate() {
+            let mut sim = Sim::new(entry.banner.clone(), entry.goal.clone(), self.seed);
+            sim.set_pool_sizes(self.pool_sizes);
+            sim.set_progress(entry.progress.clone());
+            sim.set_strategy(self.strategy);
+            campaign.add_banner(sim, entry.income);
+            campaign.set_limit(index, entry.limit);
+        }
+        campaign
+    }
+}
+
 // Update
 
 /// Event definition for the app.
@@ -132,16 +174,50 @@ pub enum Msg {
     Alert { message: String },
     /// Gather data.
     Run,
+    /// Gather data until there are this many samples in total, rounded up to
+    /// a whole number of chunks.
+    RunSamples { samples: u32 },
+    /// Change the seed used for the simulations.
+    SeedChange { seed: u64 },
+    /// Change the number of orbs available to spend.
+    BudgetChange { budget: Option<u32> },
+    /// Change where orbs come from over time, or stop showing the days of
+    /// saving needed.
+    IncomeSet { income: Option<IncomeProfile> },
+    /// Change the method used to find the results.
+    EngineChange { engine: Engine },
     /// Change the number of focus units for a given color.
     BannerFocusSizeChange { color: Color, quantity: i8 },
-    /// Change the 4* focus setting
-    BannerFourstarFocusChange { focus: Option<Color> },
-    /// Change the starting rates.
-    BannerRateChange { rates: (u8, u8) },
+    /// Change the number of 4* focus units for a given color.
+    BannerFourstarFocusSizeChange { color: Color, quantity: u8 },
+    /// Change the kind of banner, which resets the settings that its rules
+    /// decide.
+    BannerKindChange { kind: BannerKind },
+    /// Change the starting rate for one pool.
+    BannerRateEdit { pool: Pool, rate: f32 },
     /// Change whether the banner uses focus charges.
     BannerFocusChargesToggle,
+    /// Change whether the banner has a spark.
+    BannerSparkToggle,
+    /// Change the number of summons needed to earn a spark.
+    BannerSparkThresholdChange { threshold: u32 },
     /// Replace the banner with a new one.
     BannerSet { banner: Banner },
+    /// Change the name of one of the focus units of a color.
+    BannerFocusNameChange {
+        color: Color,
+        slot: usize,
+        name: String,
+    },
+    /// Change the number of units of a color in one rarity's non-focus pool,
+    /// or go back to the usual number.
+    BannerPoolOverrideChange {
+        rarity: usize,
+        color: Color,
+        size: Option<u8>,
+    },
+    /// Change which version of the summoning pools is used.
+    PoolSizesChange { pool_sizes: PoolSizes },
     /// Set the goal to a certain preset.
     GoalPresetChange { preset: GoalPreset },
     /// Set the number of copies to use for the preset.
@@ -152,12 +228,61 @@ pub enum Msg {
     GoalPartColorChange { index: usize, color: Color },
     /// Change the number of copies for an individual unit target.
     GoalPartQuantityChange { index: usize, quantity: u8 },
-    /// Add a new individual unit target.
-    GoalPartAdd { color: Color, quantity: u8 },
-    /// Change whether the individual targets all need to happen or just one.
-    GoalKindChange { kind: GoalKind },
+    /// Remove an individual unit target from the goal.
+    GoalPartRemove { index: usize },
+    /// Change which units count for an individual unit target.
+    GoalPartTargetChange { index: usize, target: PartTarget },
+    /// Pick the focus unit of an individual unit target by name, or go back
+    /// to an unnamed unit.
+    GoalPartUnitChange {
+        index: usize,
+        unit: Option<(Color, u8)>,
+    },
+    /// Add a new individual unit target to a group of the goal.
+    GoalPartAdd {
+        group: usize,
+        color: Color,
+        quantity: u8,
+    },
+    /// Add a new nested group to a group of the goal.
+    GoalGroupAdd { group: usize },
+    /// Change how many of the entries in a group of the goal need to happen.
+    GoalKindChange { group: usize, kind: GoalKind },
+    /// Toggle whether 4* copies of the 5* focus units count towards the
+    /// custom goal.
+    GoalFourstarMergesToggle,
     /// Replace the goal with a new one.
     GoalSet { goal: Goal },
+    /// Change the number of summons since the 5* rates reset.
+    ProgressPityChange { count: u32 },
+    /// Change the number of focus charges already collected.
+    ProgressFocusChargesChange { charges: u32 },
+    /// Change the number of summons made towards the next spark.
+    ProgressSparkChange { count: u32 },
+    /// Change the number of copies already owned for a part of the goal.
+    ProgressCopiesChange { index: usize, copies: u8 },
+    /// Replace the progress with a new one.
+    ProgressSet { progress: Progress },
+    /// Change the strategy for choosing which stones to pull.
+    StrategyChange { strategy: Strategy },
+    /// Find the results for each kind of strategy to compare them.
+    CompareStrategies,
+    /// Add the current banner, goal and progress to the end of the campaign.
+    CampaignAdd,
+    /// Remove a banner from the campaign.
+    CampaignRemove { index: usize },
+    /// Change the number of orbs gained before a banner of the campaign.
+    CampaignIncomeChange { index: usize, income: u32 },
+    /// Change the number of orbs available at the start of the campaign.
+    CampaignOrbsChange { orbs: u32 },
+    /// Change the most orbs to spend on a banner of the campaign.
+    CampaignLimitChange { index: usize, limit: Option<u32> },
+    /// Change how much reaching the goal of a banner of the campaign is worth.
+    CampaignWeightChange { index: usize, weight: f64 },
+    /// Simulate the campaign.
+    CampaignRun,
+    /// Search for the stop-loss limits that make the most of the campaign.
+    CampaignOptimize,
     /// Change which page of the application is open.
     PageChange(Page),
     /// Generate a permalink that saves the application's paremeters.
@@ -181,54 +306,93 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         Msg::Alert { message } => alert(&message),
         Msg::BannerFocusSizeChange { color, quantity } => {
             model.banner.focus_sizes[color as usize] = quantity;
-            model.data.clear();
-        }
-        Msg::BannerRateChange { rates } => {
-            model.banner.starting_rates = rates;
-            model.data.clear();
-            if rates == (8, 0) {
-                // Convenient handling for legendary banners, since they
-                // always have the same focus pool sizes.
-                model.banner.focus_sizes = [3, 3, 3, 3];
-            } else if rates == (6, 0) {
-                // Another special kind of banner
-                model.banner.focus_sizes = [2, 2, 2, 2];
-            }
+            model.clear_data();
+        }
+        Msg::BannerKindChange { kind } => {
+            model.banner.set_kind(kind);
+            model.clear_data();
         }
-        Msg::BannerFourstarFocusChange { focus } => {
-            model.banner.fourstar_focus = focus;
-            model.data.clear();
+        Msg::BannerRateEdit { pool, rate } => {
+            model.banner.starting_rates[pool as usize] = rate;
+            model.clear_data();
+        }
+        Msg::BannerFourstarFocusSizeChange { color, quantity } => {
+            let banner = &mut model.banner;
+            banner.fourstar_focus_sizes[color as usize] = quantity;
+            // Switch between the usual layouts with and without a 4* focus,
+            // unless the rates have already been set up for it.
+            let fourstar_focus_rate = banner.starting_rates[Pool::FourstarFocus as usize];
+            if banner.has_fourstar_focus() && fourstar_focus_rate == 0.0 {
+                banner.starting_rates = Banner::fourstar_focus_rates();
+            } else if !banner.has_fourstar_focus() && fourstar_focus_rate > 0.0 {
+                banner.starting_rates = banner.kind.rules().rates;
+            }
+            model.clear_data();
         }
         Msg::BannerFocusChargesToggle => {
             model.banner.focus_charges = !model.banner.focus_charges;
-            model.data.clear();
+            model.clear_data();
+        }
+        Msg::BannerSparkToggle => {
+            model.banner.spark_threshold = match model.banner.spark_threshold {
+                Some(_) => None,
+                None => Some(40),
+            };
+            model.clear_data();
+        }
+        Msg::BannerSparkThresholdChange { threshold } => {
+            model.banner.spark_threshold = Some(threshold);
+            model.clear_data();
         }
         Msg::BannerSet { banner } => {
             model.banner = banner;
-            model.data.clear();
+            model.clear_data();
         }
-        Msg::Run => {
-            if !model.goal.is_available(&model.banner) {
-                return;
+        Msg::BannerFocusNameChange { color, slot, name } => {
+            // Names are only labels, so the results still hold.
+            let names = &mut model.banner.focus_names[color as usize];
+            if names.len() <= slot {
+                names.resize(slot + 1, String::new());
             }
-            let mut sim = Sim::new(model.banner, model.goal.clone());
-            let mut limit = 100;
-            let perf = seed::window().performance().unwrap();
-            let start = perf.now();
-
-            // Exponential increase with a loose target of 500 ms of calculation.
-            // Time per simulation varies wildly depending on device performance
-            // and sim parameters, so it starts with a very low number and goes
-            // from there.
-            while perf.now() - start < 250.0 {
-                for _ in 0..limit {
-                    let result = sim.roll_until_goal();
-                    model.data[result] += 1;
-                }
-                limit *= 2;
+            names[slot] = name;
+            while names.last().map_or(false, String::is_empty) {
+                names.pop();
             }
-
-            model.graph_highlight = None;
+        }
+        Msg::BannerPoolOverrideChange {
+            rarity,
+            color,
+            size,
+        } => {
+            model.banner.pool_overrides[rarity][color as usize] = size;
+            model.clear_data();
+        }
+        Msg::PoolSizesChange { pool_sizes } => {
+            model.pool_sizes = pool_sizes;
+            model.clear_data();
+            // These settings are shared by every banner of the campaign.
+            model.clear_campaign_results();
+        }
+        Msg::Run => run(model, None),
+        Msg::RunSamples { samples } => run(model, Some(samples)),
+        Msg::SeedChange { seed } => {
+            model.seed = seed;
+            model.clear_data();
+            model.clear_campaign_results();
+        }
+        Msg::BudgetChange { budget } => {
+            // The chance of success only depends on the orb counts, so only
+            // the data about what happens when the budget runs out needs to
+            // be gathered again.
+            model.budget = budget;
+            model.budget_copies.clear();
+        }
+        Msg::IncomeSet { income } => {
+            // Days are worked out from the orb counts, so the data still holds.
+            model.income = income;
+        }
+        Msg::EngineChange { engine } => {
+            model.engine = engine;
         }
         Msg::GoalPresetChange { preset } => {
             let count = if let Goal::Preset(_, count) = model.goal {
@@ -238,68 +402,255 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             };
             if preset.is_available(&model.banner) {
                 model.goal = Goal::Preset(preset, count);
-                model.data.clear();
+                model.clear_data();
             }
         }
         Msg::GoalPresetQuantityChange { quantity } => {
             if let Goal::Preset(_, count) = &mut model.goal {
                 *count = quantity;
-                model.data.clear();
+                model.clear_data();
             }
         }
         Msg::GoalPartColorChange { index, color } => {
             if let Goal::Custom(custom_goal) = &mut model.goal {
-                custom_goal.goals[index].unit_color = color;
-                model.data.clear();
+                if let Some(part) = custom_goal.part_mut(index) {
+                    part.unit_color = color;
+                    part.unit = None;
+                }
+                model.clear_data();
             }
         }
         Msg::GoalMakeCustom => {
-            let mut custom = model.goal.as_custom(&model.banner);
-            // 4* focuses in custom goals are not supported
-            for part in &mut custom.goals {
-                part.four_star = false;
-            }
-            model.goal = Goal::Custom(custom);
-            model.data.clear();
+            model.goal = Goal::Custom(model.goal.as_custom(&model.banner));
+            model.clear_data();
         }
         Msg::GoalPartQuantityChange { index, quantity } => {
             if let Goal::Custom(custom_goal) = &mut model.goal {
-                if quantity == 0 {
-                    custom_goal.goals.remove(index);
-                } else {
-                    custom_goal.goals[index].num_copies = quantity;
+                // Parts are only removed with their remove button, so a count
+                // of 0 typed into a box doesn't make one disappear.
+                if let Some(part) = custom_goal.part_mut(index) {
+                    part.num_copies = quantity.max(1);
                 }
-                model.data.clear();
+                model.clear_data();
             }
         }
-        Msg::GoalPartAdd { color, quantity } => {
+        Msg::GoalPartRemove { index } => {
             if let Goal::Custom(custom_goal) = &mut model.goal {
-                custom_goal.goals.push(GoalPart {
+                custom_goal.remove_part(index);
+                // Keep the owned copies lined up with the goal parts.
+                if index < model.progress.copies_owned.len() {
+                    model.progress.copies_owned.remove(index);
+                }
+                model.clear_data();
+            }
+        }
+        Msg::GoalPartTargetChange { index, target } => {
+            if let Goal::Custom(custom_goal) = &mut model.goal {
+                if let Some(part) = custom_goal.part_mut(index) {
+                    part.target = target;
+                    // Only focus units have names.
+                    if target == PartTarget::NonFocus || target == PartTarget::AnyFivestar {
+                        part.unit = None;
+                    }
+                }
+                model.clear_data();
+            }
+        }
+        Msg::GoalPartUnitChange { index, unit } => {
+            if let Goal::Custom(custom_goal) = &mut model.goal {
+                if let Some(part) = custom_goal.part_mut(index) {
+                    match unit {
+                        Some((color, slot)) => {
+                            part.unit_color = color;
+                            part.unit = Some(slot);
+                            // Only 4* focus units can be 4* targets.
+                            if part.target == PartTarget::FourstarFocus
+                                && slot >= model.banner.fourstar_focus_sizes[color as usize]
+                            {
+                                part.target = PartTarget::Focus;
+                            }
+                        }
+                        None => part.unit = None,
+                    }
+                }
+                model.clear_data();
+            }
+        }
+        Msg::GoalPartAdd {
+            group,
+            color,
+            quantity,
+        } => {
+            if let Goal::Custom(custom_goal) = &mut model.goal {
+                let part = GoalNode::Part(GoalPart {
                     unit_color: color,
                     num_copies: quantity,
-                    four_star: false,
+                    target: PartTarget::Focus,
+                    unit: None,
                 });
-                model.data.clear();
+                if let Some(index) = custom_goal.add_node(group, part) {
+                    model.progress.insert_part(index);
+                }
+                model.clear_data();
             }
         }
-        Msg::GoalKindChange { kind } => {
+        Msg::GoalGroupAdd { group } => {
             if let Goal::Custom(custom_goal) = &mut model.goal {
-                custom_goal.kind = kind;
-                model.data.clear();
+                // Groups start out with a single unit, since an empty group
+                // can't be achieved.
+                let nested = GoalNode::Group(GoalGroup {
+                    kind: GoalKind::All,
+                    goals: vec![GoalNode::Part(GoalPart {
+                        unit_color: Color::Red,
+                        num_copies: 1,
+                        target: PartTarget::Focus,
+                        unit: None,
+                    })],
+                });
+                if let Some(index) = custom_goal.add_node(group, nested) {
+                    model.progress.insert_part(index);
+                }
+                model.clear_data();
+            }
+        }
+        Msg::GoalKindChange { group, kind } => {
+            if let Goal::Custom(custom_goal) = &mut model.goal {
+                if let Some((group_kind, _)) = custom_goal.group_mut(group) {
+                    *group_kind = kind;
+                }
+                model.clear_data();
+            }
+        }
+        Msg::GoalFourstarMergesToggle => {
+            if let Goal::Custom(custom_goal) = &mut model.goal {
+                custom_goal.fourstar_merges = !custom_goal.fourstar_merges;
+                model.clear_data();
             }
         }
         Msg::GoalSet { goal } => {
             model.goal = goal;
-            model.data.clear();
+            model.clear_data();
+        }
+        Msg::ProgressPityChange { count } => {
+            model.progress.pity_count = count;
+            model.clear_data();
+        }
+        Msg::ProgressFocusChargesChange { charges } => {
+            model.progress.focus_charges = charges;
+            model.clear_data();
+        }
+        Msg::ProgressSparkChange { count } => {
+            model.progress.spark_count = count;
+            model.clear_data();
+        }
+        Msg::ProgressCopiesChange { index, copies } => {
+            let copies_owned = &mut model.progress.copies_owned;
+            if index >= copies_owned.len() {
+                copies_owned.resize(index + 1, 0);
+            }
+            copies_owned[index] = copies;
+            model.clear_data();
+        }
+        Msg::ProgressSet { progress } => {
+            model.progress = progress;
+            model.clear_data();
+        }
+        Msg::StrategyChange { strategy } => {
+            model.strategy = strategy;
+            model.clear_data();
+            model.clear_campaign_results();
+        }
+        Msg::CompareStrategies => compare_strategies(model),
+        Msg::CampaignAdd => {
+            if model.can_run() {
+                model.campaign.push(CampaignEntry {
+                    banner: model.banner.clone(),
+                    goal: model.goal.clone(),
+                    progress: model.progress.clone(),
+                    income: 0,
+                    limit: None,
+                    weight: 1.0,
+                });
+                model.clear_campaign_results();
+            }
+        }
+        Msg::CampaignRemove { index } => {
+            if index < model.campaign.len() {
+                model.campaign.remove(index);
+                model.clear_campaign_results();
+            }
+        }
+        Msg::CampaignIncomeChange { index, income } => {
+            if let Some(entry) = model.campaign.get_mut(index) {
+                entry.income = income;
+                model.clear_campaign_results();
+            }
+        }
+        Msg::CampaignOrbsChange { orbs } => {
+            model.campaign_orbs = orbs;
+            model.clear_campaign_results();
+        }
+        Msg::CampaignLimitChange { index, limit } => {
+            if let Some(entry) = model.campaign.get_mut(index) {
+                entry.limit = limit;
+                model.clear_campaign_results();
+            }
         }
+        Msg::CampaignWeightChange { index, weight } => {
+            if let Some(entry) = model.campaign.get_mut(index) {
+                entry.weight = weight;
+                model.campaign_plans.clear();
+            }
+        }
+        Msg::CampaignRun => run_campaign(model),
+        Msg::CampaignOptimize => optimize_campaign(model),
         Msg::PageChange(page) => {
             model.curr_page = page;
         }
         Msg::Permalink => {
+            // Saving the number of samples along with the seed means that the
+            // link reproduces exactly the same results.
+            let samples = match model.engine {
+                Engine::Simulation if !model.data.is_empty() => {
+                    format!("&samples={}", model.data.iter().sum::<u32>())
+                }
+                _ => String::new(),
+            };
+            let engine = match model.engine {
+                Engine::Simulation => "",
+                Engine::Exact => "&engine=exact",
+            };
+            let budget = match model.budget {
+                Some(budget) => format!("&budget={}", budget),
+                None => String::new(),
+            };
+            let income = match model.income {
+                Some(income) => format!("&income={}", income.to_query_string()),
+                None => String::new(),
+            };
+            let progress = if model.progress != Progress::default() {
+                format!("&progress={}", model.progress.to_query_string())
+            } else {
+                String::new()
+            };
+            let strategy = if model.strategy != Strategy::default() {
+                format!("&strategy={}", model.strategy.to_query_string())
+            } else {
+                String::new()
+            };
             let url = seed::Url::new(vec![""]).search(&format!(
-                "v=3&banner={}&goal={}&run=1",
-                base64::encode(&bincode::serialize(&model.banner).unwrap()),
-                base64::encode(&bincode::serialize(&model.goal).unwrap())
+                "v={}&banner={}&pool={}&goal={}{}{}{}&seed={}{}{}{}&run=1",
+                PERMALINK_VERSION,
+                model.banner.to_query_string(),
+                model.pool_sizes.date,
+                model.goal.to_query_string(),
+                progress,
+                strategy,
+                engine,
+                model.seed,
+                samples,
+                budget,
+                income
             ));
             seed::push_route(url);
         }
@@ -309,6 +660,133 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     }
 }
 
+/// Gathers data for the current banner and goal. Simulations continue on
+/// from the data that has already been gathered, in chunks that are each
+/// seeded separately, until there are `samples` samples in total or, if no
+/// number is given, until a time limit passes. Either way it stops at the end
+/// of a chunk.
+fn run(model: &mut Model, samples: Option<u32>) {
+    if !model.can_run() {
+        return;
+    }
+    match model.engine {
+        Engine::Simulation => {
+            let mut sim = model.sim();
+            let perf = seed::window().performance().unwrap();
+            let start = perf.now();
+            let mut total = model.data.iter().sum::<u32>();
+            // Every chunk is run in full, so that the chunks a run stops
+            // after are the same ones that a permalink starts from.
+            let samples = samples
+                .map(|samples| samples.saturating_add(CHUNK_SIZE - 1) / CHUNK_SIZE * CHUNK_SIZE);
+
+            // Loose target of 250 ms of calculation. Time per simulation
+            // varies wildly depending on device performance and sim
+            // parameters, so it checks after every chunk.
+            loop {
+                let chunk_samples = match samples {
+                    Some(samples) => samples.saturating_sub(total).min(CHUNK_SIZE),
+                    None if perf.now() - start < 250.0 => CHUNK_SIZE,
+                    None => 0,
+                };
+                if chunk_samples == 0 {
+                    break;
+                }
+                let chunk = (total / CHUNK_SIZE) as u64;
+                sim.run_chunk(
+                    model.seed,
+                    chunk,
+                    chunk_samples,
+                    &mut model.data,
+                    &mut model.budget_copies,
+                );
+                total += chunk_samples;
+            }
+        }
+        Engine::Exact => {
+            // Far enough into the tail that the percentiles shown on
+            // the graph aren't affected.
+            model.exact_data = exact::orb_distribution(&model.sim(), 1e-6);
+        }
+    }
+
+    model.graph_highlight = None;
+}
+
+/// Finds the results of the current strategy along with one of each of the
+/// other kinds. Simulations use a fixed number of samples from the same seed,
+/// so that the differences come from the strategies and not from luck.
+fn compare_strategies(model: &mut Model) {
+    if !model.can_run() {
+        return;
+    }
+    let mut strategies = vec![model.strategy];
+    for strategy in Strategy::comparison_set(&model.goal.as_custom(&model.banner)) {
+        if !strategies.contains(&strategy) {
+            strategies.push(strategy);
+        }
+    }
+
+    model.comparison.clear();
+    for strategy in strategies {
+        let mut sim = model.sim();
+        sim.set_strategy(strategy);
+        let comparison = match model.engine {
+            Engine::Simulation => {
+                let mut data = Counter::default();
+                let mut budget_copies = Counter::default();
+                for chunk in 0..10 {
+                    sim.run_chunk(model.seed, chunk, CHUNK_SIZE, &mut data, &mut budget_copies);
+                }
+                Comparison::new(strategy, &data, model.budget)
+            }
+            Engine::Exact => {
+                let data = exact::orb_distribution(&sim, 1e-6);
+                Comparison::new(strategy, &data, model.budget)
+            }
+        };
+        model.comparison.push(comparison);
+    }
+}
+
+/// Simulates the campaign, using a fixed number of samples from the current
+/// seed.
+fn run_campaign(model: &mut Model) {
+    let mut campaign = model.campaign();
+    if campaign.is_empty() {
+        return;
+    }
+    model.campaign_results = CampaignResults::default();
+    for chunk in 0..10 {
+        campaign.run_chunk(model.seed, chunk, CHUNK_SIZE, &mut model.campaign_results);
+    }
+}
+
+/// Searches for the best stop-loss limits for the campaign. Each plan gets
+/// fewer samples than a normal run, since there are a lot of them to try.
+fn optimize_campaign(model: &mut Model) {
+    let campaign = model.campaign();
+    if campaign.is_empty() {
+        return;
+    }
+    let weights = model
+        .campaign
+        .iter()
+        .map(|entry| entry.weight)
+        .collect::<Vec<_>>();
+    // About twenty limits per banner, whatever the size of the stash.
+    let step = ((campaign.total_orbs() / 20).max(5) + 4) / 5 * 5;
+    let seed = model.seed;
+    model.campaign_plans = optimize_limits(&campaign, &weights, step, |campaign| {
+        let mut campaign = campaign.clone();
+        let mut results = CampaignResults::default();
+        for chunk in 0..2 {
+            campaign.run_chunk(seed, chunk, CHUNK_SIZE, &mut results);
+        }
+        results
+    });
+}
+
 // View
 
 /// Display the current state.
@@ -322,6 +800,7 @@ fn view(model: &Model) -> Vec<Node<Msg>> {
 
 /// Display the main page of the application.
 fn main_page(model: &Model) -> Vec<Node<Msg>> {
+    let goal_summary = model.goal.as_custom(&model.banner).describe(&model.banner);
     vec![
         header![
             class!["no-select"],
@@ -350,24 +829,130 @@ fn main_page(model: &Model) -> Vec<Node<Msg>> {
             class!["no-select"],
             id!["content"],
             goal::goal_selector(&model.goal, &model.banner),
-            banner::banner_selector(&model.banner),
+            banner::banner_selector(&model.banner, model.pool_sizes),
+            progress::progress_selector(&model.progress, &model.goal, &model.banner),
+            strategy::strategy_selector(model.strategy),
             div![
                 style![
                     "display" => "flex";
                     "align-items" => "center";
                 ],
+                engine_selector(model.engine),
+                if model.engine == Engine::Simulation {
+                    seed_input(model.seed)
+                } else {
+                    seed::empty()
+                },
                 button![
                     simple_ev(Ev::Click, Msg::Run),
-                    if !model.goal.is_available(&model.banner) {
+                    if !model.can_run() {
                         attrs![At::Disabled => true]
                     } else {
                         attrs![]
                     },
-                    if model.data.is_empty() { "Run" } else { "More" }
+                    if model.engine == Engine::Simulation && !model.data.is_empty() {
+                        "More"
+                    } else {
+                        "Run"
+                    }
                 ],
                 permalink(),
             ],
-            results::results(&model.data, model.graph_highlight),
+            match model.engine {
+                Engine::Simulation => results::results(
+                    &model.data,
+                    format!("{} samples", model.data.iter().sum::<u32>()),
+                    goal_summary,
+                    model.graph_highlight,
+                    model.budget,
+                    Some(&model.budget_copies),
+                    model.income,
+                ),
+                Engine::Exact => {
+                    results::results(
+                        &model.exact_data,
+                        "Exact".to_string(),
+                        goal_summary,
+                        model.graph_highlight,
+                        model.budget,
+                        None,
+                        model.income,
+                    )
+                }
+            },
+            strategy::strategy_comparison(&model.comparison),
+            campaign::campaign_planner(
+                &model.campaign,
+                model.campaign_orbs,
+                &model.campaign_results,
+                &model.campaign_plans,
+                model.can_run(),
+            ),
+        ],
+    ]
+}
+
+/// Dropdown for choosing how the results are found.
+fn engine_selector(engine: Engine) -> Node<Msg> {
+    select![
+        id!["engine"],
+        input_ev(Ev::Input, |text| match &*text {
+            "Simulation" => Msg::EngineChange {
+                engine: Engine::Simulation
+            },
+            "Exact" => Msg::EngineChange {
+                engine: Engine::Exact
+            },
+            _ => Msg::Null,
+        }),
+        option![
+            attrs![
+                At::Value => "Simulation";
+            ],
+            if engine == Engine::Simulation {
+                attrs![At::Selected => ""]
+            } else {
+                attrs![]
+            },
+            "Simulate",
+        ],
+        option![
+            attrs![
+                At::Value => "Exact";
+            ],
+            if engine == Engine::Exact {
+                attrs![At::Selected => ""]
+            } else {
+                attrs![]
+            },
+            "Exact",
+        ],
+    ]
+}
+
+/// Input for the seed of the simulations.
+fn seed_input(seed: u64) -> Node<Msg> {
+    span![
+        label![
+            attrs![
+                At::For => "seed";
+            ],
+            "Seed: ",
+        ],
+        input![
+            id!["seed"],
+            input_ev(Ev::Input, |text| {
+                if let Ok(seed) = text.parse::<u64>() {
+                    Msg::SeedChange { seed }
+                } else {
+                    Msg::Null
+                }
+            }),
+            attrs![
+                At::Type => "text";
+                At::Value => seed;
+                At::Size => 10;
+            ],
         ],
     ]
 }
@@ -425,6 +1010,16 @@ fn permalink() -> Node<Msg> {
     ]
 }
 
+/// Sets up the initial state, with a random seed so that each visit gets
+/// different results unless a seed is chosen.
+fn after_mount(_: seed::Url, _: &mut impl Orders<Msg>) -> AfterMount<Model> {
+    AfterMount::new(Model {
+        // Kept short so that it's easy to copy into a bug report.
+        seed: SmallRng::from_entropy().gen::<u32>() as u64,
+        ..Model::default()
+    })
+}
+
 /// Queue up messages based on the URL with which the application was loaded.
 fn routes(url: seed::Url) -> Option<Msg> {
     let mut messages = vec![];
@@ -436,28 +1031,114 @@ fn routes(url: seed::Url) -> Option<Msg> {
     });
 
     let mut invalid_query_string = false;
+    let version = query_string::get(&url, "v");
 
     if let Some(banner) = query_string::get(&url, "banner") {
-        if let Some(banner) = Banner::from_query_string(banner) {
+        let banner = match version {
+            Some("3") => legacy::banner_from_v3(banner),
+            _ => Banner::from_query_string(banner),
+        };
+        if let Some(banner) = banner {
             messages.push(Msg::BannerSet { banner });
         } else {
             invalid_query_string = true;
         }
     }
 
+    // Links from before the pool sizes could be chosen don't say which ones
+    // they used, but it was always the oldest ones.
+    match query_string::get(&url, "pool") {
+        Some(date) => {
+            if let Some(pool_sizes) = PoolSizes::from_date(date) {
+                messages.push(Msg::PoolSizesChange { pool_sizes });
+            } else {
+                invalid_query_string = true;
+            }
+        }
+        None if version.is_some() => {
+            messages.push(Msg::PoolSizesChange {
+                pool_sizes: PoolSizes::oldest(),
+            });
+        }
+        None => {}
+    }
+
     if let Some(goal) = query_string::get(&url, "goal") {
-        if let Some(goal) = Goal::from_query_string(goal) {
+        let goal = match version {
+            Some("3") => legacy::goal_from_v3(goal),
+            _ => Goal::from_query_string(goal),
+        };
+        if let Some(goal) = goal {
             messages.push(Msg::GoalSet { goal });
         } else {
             invalid_query_string = true;
         }
     }
 
+    if let Some(progress) = query_string::get(&url, "progress") {
+        if let Some(progress) = Progress::from_query_string(progress) {
+            messages.push(Msg::ProgressSet { progress });
+        } else {
+            invalid_query_string = true;
+        }
+    }
+
+    if let Some(strategy) = query_string::get(&url, "strategy") {
+        if let Some(strategy) = Strategy::from_query_string(strategy) {
+            messages.push(Msg::StrategyChange { strategy });
+        } else {
+            invalid_query_string = true;
+        }
+    }
+
+    match query_string::get(&url, "engine") {
+        Some("exact") => messages.push(Msg::EngineChange {
+            engine: Engine::Exact,
+        }),
+        Some("simulation") => messages.push(Msg::EngineChange {
+            engine: Engine::Simulation,
+        }),
+        Some(_) => invalid_query_string = true,
+        None => {}
+    }
+
+    if let Some(seed) = query_string::get(&url, "seed") {
+        if let Ok(seed) = seed.parse::<u64>() {
+            messages.push(Msg::SeedChange { seed });
+        } else {
+            invalid_query_string = true;
+        }
+    }
+
+    if let Some(budget) = query_string::get(&url, "budget") {
+        if let Ok(budget) = budget.parse::<u32>() {
+            messages.push(Msg::BudgetChange {
+                budget: Some(budget),
+            });
+        } else {
+            invalid_query_string = true;
+        }
+    }
+
+    if let Some(income) = query_string::get(&url, "income") {
+        if let Some(income) = IncomeProfile::from_query_string(income) {
+            messages.push(Msg::IncomeSet {
+                income: Some(income),
+            });
+        } else {
+            invalid_query_string = true;
+        }
+    }
+
     if let Some("1") = query_string::get(&url, "run") {
-        messages.push(Msg::Run);
+        match query_string::get(&url, "samples").map(str::parse::<u32>) {
+            Some(Ok(samples)) => messages.push(Msg::RunSamples { samples }),
+            Some(Err(_)) => invalid_query_string = true,
+            None => messages.push(Msg::Run),
+        }
     }
 
-    if query_string::get(&url, "v").is_some() && query_string::get(&url, "v") != Some("3") {
+    if version.is_some() && version != Some("3") && version != Some(PERMALINK_VERSION) {
         Some(Msg::Alert {
             message: "The permalink format has changed, please update your link.".into(),
         })
@@ -480,6 +1161,7 @@ extern "C" {
 #[wasm_bindgen]
 pub fn render() {
     seed::App::builder(update, view)
+        .after_mount(after_mount)
         .routes(routes)
         .build_and_start();
 }
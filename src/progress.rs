@@ -0,0 +1,145 @@
+use seed::prelude::*;
+
+use feh_sim_core::goal::{merge_level, Goal, PartTarget};
+use feh_sim_core::{Banner, Progress};
+
+use crate::goal::merge_level_options;
+use crate::Msg;
+
+/// Section for entering how far into the banner the summoner already is.
+pub fn progress_selector(progress: &Progress, goal: &Goal, banner: &Banner) -> Node<Msg> {
+    // Input for a number that stays valid when the field is blank, since
+    // starting from scratch is the usual case.
+    let number_input = |id: &str, value: u32, max: Option<u32>, msg: fn(u32) -> Msg| {
+        let mut attrs = attrs![
+            At::Id => id;
+            At::Type => "number";
+            At::Class => "small_number";
+            At::Min => 0;
+            At::Value => if value > 0 {
+                value.to_string()
+            } else {
+                "".to_string()
+            };
+        ];
+        if let Some(max) = max {
+            attrs.add(At::Max, max);
+        }
+        input![
+            input_ev(Ev::Input, move |text| msg(text.parse::<u32>().unwrap_or(0))),
+            attrs,
+        ]
+    };
+
+    let mut copies_owned = div![];
+    for (index, part) in goal.as_custom(banner).parts().iter().enumerate() {
+        let id = format!("copies_owned_{}", index);
+        let copies_changed = move |text: String| Msg::ProgressCopiesChange {
+            index,
+            copies: text.parse::<u8>().unwrap_or(0),
+        };
+        let owned = progress.copies_owned(index);
+        copies_owned.add_child(if part.target == PartTarget::AnyFivestar {
+            div![
+                label![
+                    attrs![
+                        At::For => id.clone();
+                    ],
+                    "5* units owned: ",
+                ],
+                input![
+                    id![id],
+                    input_ev(Ev::Input, copies_changed),
+                    attrs![
+                        At::Type => "number";
+                        At::Class => "small_number";
+                        At::Min => 0;
+                        At::Value => match owned {
+                            0 => "".to_string(),
+                            copies => copies.to_string(),
+                        };
+                    ],
+                ],
+                format!(" of {}", part.num_copies.max(1)),
+            ]
+        } else {
+            div![
+                label![
+                    attrs![
+                        At::For => id.clone();
+                    ],
+                    match part.unit_name(banner) {
+                        Some(name) => format!("Merge level of {}: ", name),
+                        None => format!(
+                            "Merge level of the {} {}unit: ",
+                            part.unit_color.to_string().to_lowercase(),
+                            match part.target {
+                                PartTarget::FourstarFocus => "4* ",
+                                PartTarget::NonFocus => "non-focus ",
+                                _ => "",
+                            },
+                        ),
+                    },
+                ],
+                select![
+                    id![id],
+                    input_ev(Ev::Input, copies_changed),
+                    merge_level_options(owned, 0),
+                ],
+                format!(" of +{}", merge_level(part.num_copies).unwrap_or(0)),
+            ]
+        });
+    }
+
+    div![
+        id!["progress_selector"],
+        div![
+            label![
+                attrs![
+                    At::For => "pity_count";
+                ],
+                "Summons since the 5* rates reset: ",
+            ],
+            number_input("pity_count", progress.pity_count, None, |count| {
+                Msg::ProgressPityChange { count }
+            }),
+            if banner.focus_charges {
+                span![
+                    label![
+                        attrs![
+                            At::For => "progress_focus_charges";
+                        ],
+                        " Focus charges: ",
+                    ],
+                    number_input(
+                        "progress_focus_charges",
+                        progress.focus_charges,
+                        Some(3),
+                        |charges| Msg::ProgressFocusChargesChange { charges }
+                    ),
+                ]
+            } else {
+                seed::empty()
+            },
+            if let Some(threshold) = banner.spark_threshold {
+                span![
+                    label![
+                        attrs![
+                            At::For => "spark_count";
+                        ],
+                        " Summons towards spark: ",
+                    ],
+                    number_input(
+                        "spark_count",
+                        progress.spark_count,
+                        Some(threshold.saturating_sub(1)),
+                        |count| Msg::ProgressSparkChange { count }
+                    ),
+                ]
+            } else {
+                seed::empty()
+            },
+        ],
+        copies_owned,
+    ]
+}
@@ -1,13 +1,233 @@
 use seed::prelude::*;
 
-use crate::counter::Counter;
+use std::fmt::Write;
+
 use crate::Msg;
+use feh_sim_core::counter::{Count, Counter};
+use feh_sim_core::{stats, IncomeProfile};
 
 mod svg_graph;
 
 /// Section for displaying the results. If `highlight` is given, places a label
 /// on the graph at the specified point. Otherwise, labels are placed at pre-set
-/// locations.
-pub fn results(data: &Counter, highlight: Option<f32>) -> Node<Msg> {
-    div![id!["results"], svg_graph::graph(data, highlight),]
+/// locations. `caption` describes where the data came from, and `goal`
+/// describes what the data is for. `budget_copies` is the number of goal
+/// copies that the runs which went over the budget had gotten by then, if
+/// that was tracked. If `income` is given, a second graph shows the days of
+/// saving needed.
+pub fn results<T: Count>(
+    data: &Counter<T>,
+    caption: String,
+    goal: String,
+    highlight: Option<f32>,
+    budget: Option<u32>,
+    budget_copies: Option<&Counter>,
+    income: Option<IncomeProfile>,
+) -> Node<Msg> {
+    div![
+        id!["results"],
+        if data.is_empty() {
+            seed::empty()
+        } else {
+            p![id!["goal_summary"], format!("Goal: {}", goal)]
+        },
+        budget_input(budget),
+        svg_graph::graph(data, caption, highlight, budget),
+        budget_summary(data, budget, budget_copies),
+        income_input(income),
+        match income {
+            Some(income) if income.orbs_per_day() > 0.0 => {
+                svg_graph::days_graph(data, highlight, income)
+            }
+            Some(_) => p!["With no orb income, only the orbs already saved can be spent."],
+            None => seed::empty(),
+        },
+    ]
+}
+
+/// Inputs for where orbs come from over time, which can be turned off.
+fn income_input(income: Option<IncomeProfile>) -> Node<Msg> {
+    let toggle = nodes![
+        input![
+            id!["income_enabled"],
+            simple_ev(
+                Ev::Input,
+                Msg::IncomeSet {
+                    income: match income {
+                        Some(_) => None,
+                        None => Some(IncomeProfile::default()),
+                    },
+                }
+            ),
+            attrs![At::Type => "checkbox"; At::Checked => income.is_some().as_at_value()],
+        ],
+        label![attrs![At::For => "income_enabled"], "Days of saving"],
+    ];
+    let income = match income {
+        Some(income) => income,
+        None => return div![toggle],
+    };
+
+    let profiles = [
+        ("f2p", "Free-to-play", IncomeProfile::free_to_play()),
+        ("paying", "Paying", IncomeProfile::paying()),
+    ];
+    let mut profile_select = select![input_ev(Ev::Input, move |value| {
+        match profiles.iter().find(|(name, _, _)| *name == value) {
+            // Keep the orbs that are already saved, which aren't part of
+            // the profile.
+            Some(&(_, _, profile)) => Msg::IncomeSet {
+                income: Some(IncomeProfile {
+                    saved: income.saved,
+                    ..profile
+                }),
+            },
+            None => Msg::Null,
+        }
+    })];
+    let mut matched = false;
+    for &(name, label, profile) in &profiles {
+        let mut attrs = attrs![At::Value => name];
+        let with_saved = IncomeProfile {
+            saved: income.saved,
+            ..profile
+        };
+        if with_saved == income {
+            attrs.add(At::Selected, "");
+            matched = true;
+        }
+        profile_select.add_child(option![attrs, label]);
+    }
+    if !matched {
+        profile_select.add_child(option![
+            attrs![At::Value => "custom"; At::Selected => ""],
+            "Custom",
+        ]);
+    }
+
+    // Input for one of the amounts, which updates the profile with `set`.
+    let number_input = |id: &str, label: &str, value: String, set: fn(&mut IncomeProfile, f32)| {
+        span![
+            label![
+                class!["padleft"],
+                attrs![
+                    At::For => id;
+                ],
+                label,
+            ],
+            input![
+                id![id],
+                input_ev(Ev::Input, move |text| match text.parse::<f32>() {
+                    Ok(value) if value >= 0.0 && value.is_finite() => {
+                        let mut income = income;
+                        set(&mut income, value);
+                        Msg::IncomeSet {
+                            income: Some(income),
+                        }
+                    }
+                    _ => Msg::Null,
+                }),
+                attrs![
+                    At::Type => "number";
+                    At::Class => "small_number";
+                    At::Min => 0;
+                    At::Step => "any";
+                    At::Value => value;
+                ],
+            ],
+        ]
+    };
+
+    div![
+        toggle,
+        span![class!["padleft"], profile_select],
+        number_input(
+            "income_saved",
+            "Saved: ",
+            income.saved.to_string(),
+            |income, value| income.saved = value as u32
+        ),
+        number_input(
+            "income_daily",
+            "Per day: ",
+            income.daily.to_string(),
+            |income, value| income.daily = value
+        ),
+        number_input(
+            "income_monthly_quests",
+            "Monthly quests: ",
+            income.monthly_quests.to_string(),
+            |income, value| income.monthly_quests = value as u32
+        ),
+        number_input(
+            "income_events",
+            "Events per month: ",
+            income.events.to_string(),
+            |income, value| income.events = value as u32
+        ),
+        number_input(
+            "income_purchased",
+            "Bought per month: ",
+            income.purchased.to_string(),
+            |income, value| income.purchased = value as u32
+        ),
+    ]
+}
+
+/// Input for the number of orbs available to spend.
+fn budget_input(budget: Option<u32>) -> Node<Msg> {
+    div![
+        label![
+            attrs![
+                At::For => "budget";
+            ],
+            "Orb budget: ",
+        ],
+        input![
+            id!["budget"],
+            input_ev(Ev::Input, |text| Msg::BudgetChange {
+                budget: text.parse::<u32>().ok(),
+            }),
+            attrs![
+                At::Type => "number";
+                At::Min => 0;
+                At::Value => budget.map(|budget| budget.to_string()).unwrap_or_default();
+            ],
+        ],
+    ]
+}
+
+/// Description of the chance of reaching the goal within the budget, and of
+/// how far the runs that don't make it get.
+fn budget_summary<T: Count>(
+    data: &Counter<T>,
+    budget: Option<u32>,
+    budget_copies: Option<&Counter>,
+) -> Node<Msg> {
+    let budget = match budget {
+        Some(budget) if !data.is_empty() => budget,
+        _ => return seed::empty(),
+    };
+    let success = stats::cdf(data, budget);
+    let mut text = format!(
+        "With {} orbs, there is a {:.1}% chance of reaching the goal.",
+        budget,
+        success * 100.0
+    );
+    match budget_copies {
+        Some(copies) if success < 1.0 => {
+            if copies.is_empty() {
+                text.push_str(" Press \"More\" to see how far you get if the orbs run out first.");
+            } else {
+                write!(
+                    text,
+                    " If the orbs run out first, you get {:.2} copies of the units in the goal on average.",
+                    stats::mean(copies)
+                )
+                .unwrap();
+            }
+        }
+        _ => {}
+    }
+    p![id!["budget_summary"], text]
 }
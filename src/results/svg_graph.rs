@@ -4,9 +4,11 @@ use std::fmt::Write;
 
 use wasm_bindgen::JsCast;
 
-use crate::counter::Counter;
-use crate::stats;
 use crate::Msg;
+use feh_sim_core::counter::{Count, Counter};
+use feh_sim_core::stats;
+use feh_sim_core::IncomeProfile;
+
 const XMIN: f32 = 0.0;
 const YMIN: f32 = 0.0;
 const WIDTH: f32 = 100.0;
@@ -14,9 +16,18 @@ const HEIGHT: f32 = 60.0;
 
 /// SVG elements for displaying the results within the graph. If `highlight` is
 /// given, places a label on the graph at the specified point. Otherwise, labels
-/// are placed at pre-set locations. Returns two elements, one for the line and
-/// one for the collection of labels.
-fn graph_line(data: &Counter, highlight: Option<f32>) -> (Node<Msg>, Node<Msg>) {
+/// are placed at pre-set locations. If `budget` is given and fits on the graph,
+/// it is marked with a line and a label for the chance of success. The orb
+/// counts are plotted after being converted by `scale` into the given `unit`.
+/// Returns two elements, one for the line and one for the collection of
+/// labels.
+fn graph_line<T: Count>(
+    data: &Counter<T>,
+    highlight: Option<f32>,
+    budget: Option<u32>,
+    scale: impl Fn(u32) -> u32,
+    unit: &str,
+) -> (Node<Msg>, Node<Msg>) {
     // Sample every 0.1% in ranges 0%-10% and 90%-100%, and every 1% in between.
     // Probabilities only change sharply near the extremes, so this makes things
     // render more quickly without hurting smoothness.
@@ -25,7 +36,10 @@ fn graph_line(data: &Counter, highlight: Option<f32>) -> (Node<Msg>, Node<Msg>)
         .chain((10..90).map(|x| x as f32 / 100.0))
         .chain((900..1000).map(|x| x as f32 / 1000.0))
         .collect::<Vec<_>>();
-    let data_points = stats::percentiles(data, &sample_points);
+    let data_points = stats::percentiles(data, &sample_points)
+        .into_iter()
+        .map(&scale)
+        .collect::<Vec<_>>();
 
     // Helper functions for converting between data values and graph coordinates.
     let x = |pct: f32| pct as f32 * WIDTH + XMIN;
@@ -56,20 +70,20 @@ fn graph_line(data: &Counter, highlight: Option<f32>) -> (Node<Msg>, Node<Msg>)
         }
     }
     let path_el = path![
-        id!["graph_line"],
+        class!["graph_line"],
         attrs![
             "d" => path;
         ],
     ];
-    let mut points_el = g![id!["graph_highlights"],];
+    let mut points_el = g![class!["graph_highlights"],];
     let mut add_point = |pct: f32| {
-        let value = stats::percentile(data, pct) as f32;
+        let value = scale(stats::percentile(data, pct)) as f32;
         points_el.add_child(circle![attrs![
             "cx" => x(pct);
             "cy" => y(value);
             "r" => "0.75px";
         ]]);
-        let label_text = format!("{}%: {} orbs", (pct * 1000.0).round() / 10.0, value);
+        let label_text = format!("{}%: {} {}", (pct * 1000.0).round() / 10.0, value, unit);
         points_el.add_child(text![
             attrs![
                 "font-size" => "15%";
@@ -98,7 +112,7 @@ fn graph_line(data: &Counter, highlight: Option<f32>) -> (Node<Msg>, Node<Msg>)
                     // the graph line.
                     attrs![
                         "dx" => 1.0;
-                        "dy" => y(stats::percentile(data, 0.24) as f32) - 1.0;
+                        "dy" => y(scale(stats::percentile(data, 0.24)) as f32) - 1.0;
                         "text-anchor" => "begin";
                         "dominant-baseline" => "baseline";
                     ]
@@ -116,14 +130,77 @@ fn graph_line(data: &Counter, highlight: Option<f32>) -> (Node<Msg>, Node<Msg>)
             }
         }
     }
+    match budget {
+        Some(budget) if !data.is_empty() && budget <= *data_points.last().unwrap() => {
+            let pct = stats::cdf(data, budget) as f32;
+            points_el.add_child(g![
+                id!["graph_budget"],
+                line_![attrs![
+                    "x1" => XMIN;
+                    "x2" => XMIN + WIDTH;
+                    "y1" => y(budget as f32);
+                    "y2" => y(budget as f32);
+                ]],
+                circle![attrs![
+                    "cx" => x(pct);
+                    "cy" => y(budget as f32);
+                    "r" => "0.75px";
+                ]],
+                text![
+                    attrs![
+                        "font-size" => "15%";
+                        "dx" => x(pct) + 1.0;
+                        "dy" => y(budget as f32) + 1.0;
+                        "text-anchor" => "begin";
+                        "dominant-baseline" => "hanging";
+                    ],
+                    format!("{} orbs: {:.1}%", budget, pct * 100.0),
+                ],
+            ]);
+        }
+        _ => {}
+    }
     (path_el, points_el)
 }
 
 /// Graph for displaying the results. If `highlight` is given, places a label
 /// on the graph at the specified point. Otherwise, labels are placed at pre-set
-/// locations.
-pub fn graph(data: &Counter, highlight: Option<f32>) -> Node<Msg> {
-    let (path_el, points_el) = graph_line(data, highlight);
+/// locations. `caption` is shown in the corner. If `budget` is given, it is
+/// marked on the graph.
+pub fn graph<T: Count>(
+    data: &Counter<T>,
+    caption: String,
+    highlight: Option<f32>,
+    budget: Option<u32>,
+) -> Node<Msg> {
+    let (path_el, points_el) = graph_line(data, highlight, budget, |orbs| orbs, "orbs");
+    graph_svg("graph", data.is_empty(), caption, path_el, points_el)
+}
+
+/// Graph of the number of days of saving needed for each chance of reaching
+/// the goal, with the same labels as the main graph. Only makes sense when
+/// `income` gains some orbs every day.
+pub fn days_graph<T: Count>(
+    data: &Counter<T>,
+    highlight: Option<f32>,
+    income: IncomeProfile,
+) -> Node<Msg> {
+    // Every number of orbs is reached eventually when there is some income.
+    let days = |orbs| income.days_needed(orbs).unwrap_or(0);
+    let (path_el, points_el) = graph_line(data, highlight, None, days, "days");
+    let caption = format!("Days of saving at {:.1} orbs a day", income.orbs_per_day());
+    graph_svg("days_graph", data.is_empty(), caption, path_el, points_el)
+}
+
+/// The frame around the contents of a graph, which places a label where it is
+/// clicked.
+fn graph_svg(
+    id: &str,
+    is_empty: bool,
+    caption: String,
+    path_el: Node<Msg>,
+    points_el: Node<Msg>,
+) -> Node<Msg> {
     fn get_graph_width(event: &web_sys::Event) -> Option<f64> {
         let target = event.target()?;
         let target_el: &web_sys::Element = target.dyn_ref::<web_sys::SvgsvgElement>()?.as_ref();
@@ -131,7 +208,8 @@ pub fn graph(data: &Counter, highlight: Option<f32>) -> Node<Msg> {
         Some(width)
     }
     svg![
-        id!["graph"],
+        id![id],
+        class!["graph"],
         mouse_ev(Ev::Click, |click| {
             if let Some(width) = get_graph_width(&click) {
                 let width_frac = (click.offset_x() as f32 / width as f32).min(0.999).max(0.0);
@@ -146,14 +224,14 @@ pub fn graph(data: &Counter, highlight: Option<f32>) -> Node<Msg> {
             At::ViewBox => format!("{} {} {} {}", XMIN, YMIN, WIDTH, HEIGHT);
         ],
         path_el,
-        if !data.is_empty() {
+        if !is_empty {
             text![
-                id!["graph_sample_count"],
+                class!["graph_caption"],
                 attrs![
                     "dominant-baseline" => "hanging";
                     "font-size" => "10%";
                 ],
-                format!("{} samples", data.iter().sum::<u32>()),
+                caption,
             ]
         } else {
             seed::empty()
@@ -0,0 +1,193 @@
+use seed::prelude::*;
+
+use std::convert::TryFrom;
+
+use strum::IntoEnumIterator;
+
+use feh_sim_core::counter::{Count, Counter};
+use feh_sim_core::stats;
+use feh_sim_core::strategy::Strategy;
+use feh_sim_core::Color;
+
+use crate::Msg;
+
+/// Percentiles shown for each strategy in a comparison.
+const COMPARISON_PERCENTILES: [f32; 3] = [0.5, 0.9, 0.99];
+
+/// Summary of the results of one strategy, for comparing it to the others.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub strategy: Strategy,
+    pub mean: f64,
+    pub percentiles: Vec<u32>,
+    /// The chance of reaching the goal within the budget, if there is one.
+    pub budget_success: Option<f64>,
+}
+
+impl Comparison {
+    pub fn new<T: Count>(strategy: Strategy, data: &Counter<T>, budget: Option<u32>) -> Self {
+        Comparison {
+            strategy,
+            mean: stats::mean(data),
+            percentiles: stats::percentiles(data, &COMPARISON_PERCENTILES),
+            budget_success: budget.map(|budget| stats::cdf(data, budget)),
+        }
+    }
+}
+
+/// Section for choosing which stones to pull in each session.
+pub fn strategy_selector(strategy: Strategy) -> Node<Msg> {
+    let option = |value: &str, label: &str, selected: bool| {
+        option![
+            attrs![
+                At::Value => value;
+            ],
+            if selected {
+                attrs![At::Selected => ""]
+            } else {
+                attrs![]
+            },
+            label,
+        ]
+    };
+    div![
+        id!["strategy_selector"],
+        label![
+            attrs![
+                At::For => "strategy";
+            ],
+            "Pull: ",
+        ],
+        select![
+            id!["strategy"],
+            input_ev(Ev::Input, |text| {
+                let strategy = match &*text {
+                    "GoalColors" => Strategy::GoalColors,
+                    "FullCircle" => Strategy::FullCircle,
+                    "SingleColor" => Strategy::SingleColor(Color::Red),
+                    "ExtraAtHighPity" => Strategy::ExtraAtHighPity(60),
+                    "OffColor" => Strategy::OffColor(Color::Colorless),
+                    _ => return Msg::Null,
+                };
+                Msg::StrategyChange { strategy }
+            }),
+            option(
+                "GoalColors",
+                "Colors in the goal",
+                strategy == Strategy::GoalColors
+            ),
+            option(
+                "FullCircle",
+                "Every stone",
+                strategy == Strategy::FullCircle
+            ),
+            option(
+                "SingleColor",
+                "Only one color",
+                matches!(strategy, Strategy::SingleColor(_))
+            ),
+            option(
+                "ExtraAtHighPity",
+                "Colors in the goal, plus one extra at high pity",
+                matches!(strategy, Strategy::ExtraAtHighPity(_))
+            ),
+            option(
+                "OffColor",
+                "Colors in the goal, or else one color",
+                matches!(strategy, Strategy::OffColor(_))
+            ),
+        ],
+        match strategy {
+            Strategy::SingleColor(selected) | Strategy::OffColor(selected) => {
+                let with_color = match strategy {
+                    Strategy::SingleColor(_) => Strategy::SingleColor,
+                    _ => Strategy::OffColor,
+                };
+                let mut color_select = select![input_ev(Ev::Input, move |value| {
+                    if let Some(color) = value
+                        .parse::<u8>()
+                        .ok()
+                        .and_then(|num| Color::try_from(num).ok())
+                    {
+                        Msg::StrategyChange {
+                            strategy: with_color(color),
+                        }
+                    } else {
+                        Msg::Null
+                    }
+                })];
+                for color in Color::iter() {
+                    let mut attrs = attrs![At::Value => color as usize];
+                    if color == selected {
+                        attrs.add(At::Selected, "");
+                    }
+                    color_select.add_child(option![attrs, color.to_string()]);
+                }
+                span![class!["padleft"], color_select]
+            }
+            Strategy::ExtraAtHighPity(summons) => span![
+                label![
+                    attrs![
+                        At::For => "strategy_summons";
+                    ],
+                    " after ",
+                ],
+                input![
+                    id!["strategy_summons"],
+                    input_ev(Ev::Input, |text| {
+                        if let Ok(summons) = text.parse::<u32>() {
+                            Msg::StrategyChange {
+                                strategy: Strategy::ExtraAtHighPity(summons),
+                            }
+                        } else {
+                            Msg::Null
+                        }
+                    }),
+                    attrs![
+                        At::Type => "number";
+                        At::Class => "small_number";
+                        At::Min => 0;
+                        At::Value => summons;
+                    ],
+                ],
+                " summons without a 5*",
+            ],
+            _ => seed::empty(),
+        },
+        button![
+            class!["padleft"],
+            simple_ev(Ev::Click, Msg::CompareStrategies),
+            "Compare",
+        ],
+    ]
+}
+
+/// Table comparing the results of different strategies.
+pub fn strategy_comparison(comparisons: &[Comparison]) -> Node<Msg> {
+    if comparisons.is_empty() {
+        return seed::empty();
+    }
+    let has_budget = comparisons[0].budget_success.is_some();
+    let mut header = tr![th!["Strategy"], th!["Average"]];
+    for pct in COMPARISON_PERCENTILES.iter() {
+        header.add_child(th![format!("{}%", pct * 100.0)]);
+    }
+    if has_budget {
+        header.add_child(th!["Within budget"]);
+    }
+    let mut table = table![id!["strategy_comparison"], header];
+    for comparison in comparisons {
+        let mut row = tr![
+            td![comparison.strategy.to_string()],
+            td![format!("{:.1}", comparison.mean)],
+        ];
+        for value in &comparison.percentiles {
+            row.add_child(td![value.to_string()]);
+        }
+        if let Some(success) = comparison.budget_success {
+            row.add_child(td![format!("{:.1}%", success * 100.0)]);
+        }
+        table.add_child(row);
+    }
+    table
+}